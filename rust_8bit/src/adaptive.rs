@@ -0,0 +1,483 @@
+use image::{ImageBuffer, Rgb};
+use std::collections::HashMap;
+
+// Count how many pixels use each distinct color, sorted by color so results are deterministic
+pub fn histogram(img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Vec<(Rgb<u8>, u32)> {
+    let mut counts: HashMap<[u8; 3], u32> = HashMap::new();
+    for pixel in img.pixels() {
+        *counts.entry(pixel.0).or_insert(0) += 1;
+    }
+    
+    let mut histogram: Vec<(Rgb<u8>, u32)> = counts.into_iter()
+        .map(|(color, count)| (Rgb(color), count))
+        .collect();
+    histogram.sort_by_key(|(color, _)| color.0);
+    histogram
+}
+
+// Order palette colors by how many pixels of the image are nearest to them, most used first.
+// Ties keep their palette order.
+pub fn by_population(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, palette: Vec<Rgb<u8>>) -> Vec<Rgb<u8>> {
+    let mut counts = vec![0u64; palette.len()];
+    for (color, count) in histogram(img) {
+        counts[crate::find_nearest_index(&color, &palette)] += count as u64;
+    }
+    
+    let mut ranked: Vec<(Rgb<u8>, u64)> = palette.into_iter().zip(counts).collect();
+    ranked.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+    ranked.into_iter().map(|(color, _)| color).collect()
+}
+
+// Build a palette from the `size` most used colors of the image. With `bits` below 8 colors are
+// first bucketed on their top `bits` bits per channel and each bucket stands for the average of
+// its pixels, so near-identical shades pool their counts. Ties keep color order.
+pub fn popularity(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, size: usize, bits: u8) -> Vec<Rgb<u8>> {
+    let shift = 8 - bits as u32;
+    let mut buckets: HashMap<[u8; 3], ([u64; 3], u32)> = HashMap::new();
+    for (color, count) in histogram(img) {
+        let (sum, total) = buckets.entry(color.0.map(|c| c >> shift)).or_insert(([0; 3], 0));
+        for c in 0..3 {
+            sum[c] += color[c] as u64 * count as u64;
+        }
+        *total += count;
+    }
+    
+    let mut ranked: Vec<_> = buckets.into_iter().collect();
+    ranked.sort_by_key(|&(bucket, (_, count))| (std::cmp::Reverse(count), bucket));
+    ranked.into_iter()
+        .take(size)
+        .map(|(_, (sum, count))| Rgb(sum.map(|s| ((s + count as u64 / 2) / count as u64) as u8)))
+        .collect()
+}
+
+// Range `estimate_size` picks from
+const AUTO_SIZE_MIN: usize = 2;
+const AUTO_SIZE_MAX: usize = 256;
+// Bits per channel colors are bucketed to for `estimate_size`, so noise and the many shades of a
+// smooth gradient don't each count as a color of their own
+const AUTO_SIZE_BITS: u32 = 4;
+
+// Estimate how many colors `img` needs from the entropy of its color histogram. 2 to the power of
+// the entropy is the number of equally common colors that would carry as much information, which
+// stays low for flat artwork however many stray shades its edges have, and saturates for photos.
+pub fn estimate_size(img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> usize {
+    let shift = 8 - AUTO_SIZE_BITS;
+    let mut buckets: HashMap<[u8; 3], u32> = HashMap::new();
+    for (color, count) in histogram(img) {
+        *buckets.entry(color.0.map(|c| c >> shift)).or_insert(0) += count;
+    }
+    
+    let total = buckets.values().map(|&count| count as f64).sum::<f64>().max(1.0);
+    let entropy: f64 = buckets.values()
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum();
+    (entropy.exp2().ceil() as usize).clamp(AUTO_SIZE_MIN, AUTO_SIZE_MAX)
+}
+
+// Build a palette of up to `size` colors by recursively splitting the image's color
+// histogram at the median of its widest channel
+pub fn median_cut(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, size: usize) -> Vec<Rgb<u8>> {
+    if size == 0 {
+        return Vec::new();
+    }
+    
+    let mut boxes = vec![histogram(img)];
+    
+    while boxes.len() < size {
+        // Pick the box spanning the widest channel range, boxes with a single color can't be split
+        let widest = boxes.iter()
+            .enumerate()
+            .filter(|(_, colors)| colors.len() > 1)
+            .map(|(index, colors)| {
+                let (channel, range) = widest_channel(colors);
+                (index, channel, range)
+            })
+            .max_by_key(|&(_, _, range)| range);
+        let (index, channel) = match widest {
+            Some((index, channel, _)) => (index, channel),
+            None => break,
+        };
+        
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_by_key(|(color, _)| color[channel]);
+        
+        // Split where half of the box's pixels fall on each side
+        let total: u64 = colors.iter().map(|&(_, count)| count as u64).sum();
+        let mut seen = 0;
+        let mut split = colors.len() / 2;
+        for (i, &(_, count)) in colors.iter().enumerate() {
+            seen += count as u64;
+            if seen * 2 >= total {
+                split = i + 1;
+                break;
+            }
+        }
+        let upper = colors.split_off(split.clamp(1, colors.len() - 1));
+        
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+    
+    boxes.iter().map(|colors| average_color(colors)).collect()
+}
+
+// Find the channel with the largest spread of values in a set of colors
+fn widest_channel(colors: &[(Rgb<u8>, u32)]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let min = colors.iter().map(|(color, _)| color[channel]).min().unwrap_or(0);
+            let max = colors.iter().map(|(color, _)| color[channel]).max().unwrap_or(0);
+            (channel, max - min)
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap_or((0, 0))
+}
+
+// Pixel-count weighted average of a set of colors
+fn average_color(colors: &[(Rgb<u8>, u32)]) -> Rgb<u8> {
+    let mut sum = [0u64; 3];
+    let mut total = 0u64;
+    for &(color, count) in colors {
+        for c in 0..3 {
+            sum[c] += color[c] as u64 * count as u64;
+        }
+        total += count as u64;
+    }
+    let total = total.max(1);
+    Rgb(sum.map(|s| ((s + total / 2) / total) as u8))
+}
+
+// Freely chosen colors of the Amiga's Extra Half-Brite mode, the other half of its 64 are these at half brightness
+const EHB_BASE_COLORS: usize = 32;
+
+// Build an Amiga Extra Half-Brite palette: 32 median cut colors followed by each of them with every
+// channel halved, in the same order so entry `i + 32` is always the half-brite copy of entry `i`.
+// Images with fewer than 32 colors repeat the last one (black for an empty image) to fill the base.
+pub fn extra_half_brite(img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Vec<Rgb<u8>> {
+    let mut palette = median_cut(img, EHB_BASE_COLORS);
+    let filler = palette.last().copied().unwrap_or(Rgb([0, 0, 0]));
+    palette.resize(EHB_BASE_COLORS, filler);
+    let half_brite: Vec<Rgb<u8>> = palette.iter().map(|color| Rgb(color.0.map(|c| c / 2))).collect();
+    palette.extend(half_brite);
+    palette
+}
+
+// Maximum number of pixels k-means clusters, larger images are randomly sampled down to this
+const KMEANS_SAMPLE_SIZE: usize = 20_000;
+
+// Seed for k-means sampling and centroid initialization when none is given, so output is reproducible
+pub const DEFAULT_SEED: u64 = 0x8B17_C0DE;
+
+// Small xorshift generator, good enough for sampling pixels and picking centroids
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // Xorshift gets stuck on zero, and nearby seeds should still diverge quickly
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+    
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+    
+    // Uniform float in [0, 1)
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+    
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+// Build a palette of `size` colors by k-means clustering the image's pixels in RGB space
+//
+// Centroids are seeded with k-means++ and refined until no centroid moves further than
+// `tolerance` or `iterations` rounds have run. The same `seed` always gives the same palette.
+pub fn kmeans(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, size: usize, iterations: usize, tolerance: f32, seed: u64) -> Vec<Rgb<u8>> {
+    let mut rng = Rng::new(seed);
+    
+    let pixels: Vec<[f32; 3]> = img.pixels().map(|p| p.0.map(|c| c as f32)).collect();
+    let samples: Vec<[f32; 3]> = if pixels.len() > KMEANS_SAMPLE_SIZE {
+        (0..KMEANS_SAMPLE_SIZE).map(|_| pixels[rng.below(pixels.len())]).collect()
+    } else {
+        pixels
+    };
+    if samples.is_empty() || size == 0 {
+        return Vec::new();
+    }
+    
+    // k-means++: each new centroid is picked with probability proportional to its squared
+    // distance from the nearest centroid chosen so far
+    let mut centroids = vec![samples[rng.below(samples.len())]];
+    let mut distances: Vec<f32> = samples.iter().map(|s| squared_distance(s, &centroids[0])).collect();
+    while centroids.len() < size {
+        let total: f32 = distances.iter().sum();
+        if total <= 0.0 {
+            // Fewer distinct colors than requested
+            break;
+        }
+        let mut target = rng.next_f32() * total;
+        let mut chosen = samples.len() - 1;
+        for (i, &d) in distances.iter().enumerate() {
+            if target < d {
+                chosen = i;
+                break;
+            }
+            target -= d;
+        }
+        let centroid = samples[chosen];
+        for (d, sample) in distances.iter_mut().zip(&samples) {
+            *d = d.min(squared_distance(sample, &centroid));
+        }
+        centroids.push(centroid);
+    }
+    
+    for _ in 0..iterations {
+        let mut sums = vec![[0f32; 3]; centroids.len()];
+        let mut counts = vec![0u32; centroids.len()];
+        for sample in &samples {
+            let nearest = nearest_centroid(sample, &centroids);
+            for c in 0..3 {
+                sums[nearest][c] += sample[c];
+            }
+            counts[nearest] += 1;
+        }
+        
+        // Empty clusters keep their previous centroid
+        let mut movement: f32 = 0.0;
+        for (i, centroid) in centroids.iter_mut().enumerate() {
+            if counts[i] == 0 {
+                continue;
+            }
+            let mean = sums[i].map(|s| s / counts[i] as f32);
+            movement = movement.max(squared_distance(centroid, &mean).sqrt());
+            *centroid = mean;
+        }
+        if movement < tolerance {
+            break;
+        }
+    }
+    
+    centroids.iter()
+        .map(|centroid| Rgb(centroid.map(|c| c.round().clamp(0.0, 255.0) as u8)))
+        .collect()
+}
+
+fn nearest_centroid(sample: &[f32; 3], centroids: &[[f32; 3]]) -> usize {
+    let mut best = 0;
+    let mut best_distance = f32::MAX;
+    for (i, centroid) in centroids.iter().enumerate() {
+        let d = squared_distance(sample, centroid);
+        if d < best_distance {
+            best = i;
+            best_distance = d;
+        }
+    }
+    best
+}
+
+fn squared_distance(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    let dr = a[0] - b[0];
+    let dg = a[1] - b[1];
+    let db = a[2] - b[2];
+    dr * dr + dg * dg + db * db
+}
+
+// One bit of each channel is consumed per level, so leaves sit at depth 8
+const OCTREE_DEPTH: usize = 8;
+
+struct OctreeNode {
+    children: [Option<usize>; 8],
+    sum: [u64; 3],
+    count: u64,
+    leaf: bool,
+}
+
+impl OctreeNode {
+    fn new() -> Self {
+        OctreeNode { children: [None; 8], sum: [0; 3], count: 0, leaf: false }
+    }
+}
+
+// Build a palette of at most `size` colors with octree quantization
+//
+// Every distinct color is inserted as a leaf, then the least populated branches are folded
+// into their parents, deepest level first, until no more than `size` leaves remain.
+pub fn octree(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, size: usize) -> Vec<Rgb<u8>> {
+    let histogram = histogram(img);
+    if size == 0 {
+        return Vec::new();
+    }
+    
+    // Nodes live in an arena, `levels` tracks the branch nodes created at each depth
+    let mut nodes = vec![OctreeNode::new()];
+    let mut levels: Vec<Vec<usize>> = vec![Vec::new(); OCTREE_DEPTH];
+    levels[0].push(0);
+    
+    for &(color, count) in &histogram {
+        let mut node = 0;
+        for depth in 0..OCTREE_DEPTH {
+            let bit = 7 - depth;
+            let child = (((color[0] >> bit) & 1) << 2 | ((color[1] >> bit) & 1) << 1 | ((color[2] >> bit) & 1)) as usize;
+            node = match nodes[node].children[child] {
+                Some(existing) => existing,
+                None => {
+                    nodes.push(OctreeNode::new());
+                    let created = nodes.len() - 1;
+                    nodes[node].children[child] = Some(created);
+                    if depth + 1 < OCTREE_DEPTH {
+                        levels[depth + 1].push(created);
+                    }
+                    created
+                }
+            };
+        }
+        
+        let leaf = &mut nodes[node];
+        for c in 0..3 {
+            leaf.sum[c] += color[c] as u64 * count as u64;
+        }
+        leaf.count += count as u64;
+        leaf.leaf = true;
+    }
+    
+    // Working from the bottom up, every child of a branch at `depth` is already a leaf
+    let mut leaves = histogram.len();
+    for depth in (0..OCTREE_DEPTH).rev() {
+        if leaves <= size {
+            break;
+        }
+        
+        let children_total = |nodes: &[OctreeNode], index: usize| -> u64 {
+            nodes[index].children.iter().flatten().map(|&child| nodes[child].count).sum()
+        };
+        let mut branches = levels[depth].clone();
+        branches.sort_by_key(|&index| children_total(&nodes, index));
+        
+        for index in branches {
+            if leaves <= size {
+                break;
+            }
+            let mut children: Vec<usize> = nodes[index].children.iter().flatten().copied().collect();
+            
+            // Folding the whole branch would leave fewer colors than requested,
+            // so only merge its least populated children into one
+            let excess = leaves - size;
+            if children.len() - 1 > excess {
+                children.sort_by_key(|&child| nodes[child].count);
+                let target = children[0];
+                for &child in &children[1..=excess] {
+                    merge_into(&mut nodes, target, child);
+                    for slot in nodes[index].children.iter_mut() {
+                        if *slot == Some(child) {
+                            *slot = None;
+                        }
+                    }
+                }
+                leaves -= excess;
+                break;
+            }
+            
+            for &child in &children {
+                merge_into(&mut nodes, index, child);
+            }
+            nodes[index].children = [None; 8];
+            nodes[index].leaf = true;
+            leaves -= children.len() - 1;
+        }
+    }
+    
+    // Collect the averaged colors of the remaining leaves
+    let mut palette = Vec::with_capacity(leaves);
+    let mut stack = vec![0];
+    while let Some(index) = stack.pop() {
+        let node = &nodes[index];
+        if node.leaf {
+            let count = node.count.max(1);
+            palette.push(Rgb(node.sum.map(|s| ((s + count / 2) / count) as u8)));
+        } else {
+            stack.extend(node.children.iter().flatten());
+        }
+    }
+    palette
+}
+
+// Add the pixels counted in `source` to `target`
+fn merge_into(nodes: &mut [OctreeNode], target: usize, source: usize) {
+    let (sum, count) = (nodes[source].sum, nodes[source].count);
+    for (total, s) in nodes[target].sum.iter_mut().zip(sum) {
+        *total += s;
+    }
+    nodes[target].count += count;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // Image of uniformly random colors
+    fn noise(width: u32, height: u32, seed: u64) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        let mut rng = Rng::new(seed);
+        ImageBuffer::from_fn(width, height, |_, _| Rgb([0; 3].map(|_: u8| rng.below(256) as u8)))
+    }
+    
+    #[test]
+    fn octree_stays_within_size() {
+        let img = noise(64, 64, 1);
+        for size in [1, 2, 7, 16, 64, 255, 256] {
+            let palette = octree(&img, size);
+            assert!(!palette.is_empty() && palette.len() <= size, "{} colors for size {}", palette.len(), size);
+        }
+    }
+    
+    #[test]
+    fn kmeans_is_reproducible_per_seed() {
+        // Over KMEANS_SAMPLE_SIZE pixels, so the seed picks the sample as well as the first centroids
+        let img = noise(160, 150, 2);
+        let palette = kmeans(&img, 16, 10, 0.5, DEFAULT_SEED);
+        assert_eq!(palette.len(), 16);
+        assert!(palette == kmeans(&img, 16, 10, 0.5, DEFAULT_SEED));
+        // Different seeds start from other samples and settle on other local optima
+        assert!((1..=4).any(|seed| kmeans(&img, 16, 10, 0.5, DEFAULT_SEED + seed) != palette));
+    }
+    
+    #[test]
+    fn popularity_ignores_rare_colors_median_cut_does_not() {
+        // Three flat areas with a sprinkle of one-off colors, every 8th pixel
+        let flat = [Rgb([200, 40, 40]), Rgb([40, 160, 60]), Rgb([30, 30, 90])];
+        let sprinkle = noise(48, 48, 3);
+        let img = ImageBuffer::from_fn(48, 48, |x, y| if (x + y * 48) % 8 == 0 { *sprinkle.get_pixel(x, y) } else { flat[(x / 16) as usize] });
+        
+        let mut popular = popularity(&img, 3, 8);
+        popular.sort_by_key(|color| color.0);
+        let mut expected = flat.to_vec();
+        expected.sort_by_key(|color| color.0);
+        assert!(popular == expected);
+        // Median cut splits the whole color range the sprinkle spans, so its boxes average the flat
+        // colors together with rare ones
+        let cut = median_cut(&img, 3);
+        assert!(flat.iter().any(|color| !cut.contains(color)));
+    }
+    
+    #[test]
+    fn half_brite_entries_halve_the_first_32() {
+        let two_tone = ImageBuffer::from_fn(16, 16, |x, _| if x < 8 { Rgb([250, 10, 10]) } else { Rgb([9, 99, 201]) });
+        // Too few colors to fill the base are padded, the layout never changes
+        let images = [noise(64, 64, 4), two_tone, ImageBuffer::from_pixel(4, 4, Rgb([77, 77, 77])), ImageBuffer::new(0, 0)];
+        for img in images {
+            let palette = extra_half_brite(&img);
+            assert_eq!(palette.len(), 2 * EHB_BASE_COLORS);
+            for i in 0..EHB_BASE_COLORS {
+                assert_eq!(palette[i + EHB_BASE_COLORS].0, palette[i].0.map(|c| c / 2));
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use image::{ImageBuffer, Rgb};
+use rayon::prelude::*;
+use crate::color::{hsv_to_rgb, rgb_to_hsv};
+use crate::Options;
+
+// Tone adjustments applied to the source image before it is pixelated and quantized.
+// Each step is skipped when its options are at their no-op defaults.
+pub fn adjust(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, options: &Options) {
+    if options.brightness != 0.0 || options.contrast != 1.0 {
+        brightness_contrast(img, options.brightness, options.contrast);
+    }
+    if options.temperature != 0.0 {
+        temperature(img, options.temperature);
+    }
+    if options.saturation != 1.0 || options.hue_shift != 0.0 {
+        saturation_hue(img, options.saturation, options.hue_shift);
+    }
+    if options.sepia {
+        sepia(img);
+    }
+    if options.invert {
+        map_channels(img, |c| 255 - c);
+    }
+}
+
+// Whether every adjustment is at its no-op default, so `adjust` leaves the image untouched
+pub fn is_identity(options: &Options) -> bool {
+    options.brightness == 0.0 && options.contrast == 1.0 && options.temperature == 0.0 && options.saturation == 1.0 && options.hue_shift == 0.0 && !options.sepia && !options.invert
+}
+
+// Apply `out = (in - 0.5) * contrast + 0.5 + brightness` to every channel, working in 0.0-1.0
+fn brightness_contrast(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, brightness: f32, contrast: f32) {
+    map_channels(img, |c| (((c as f32 / 255.0 - 0.5) * contrast + 0.5 + brightness).clamp(0.0, 1.0) * 255.0).round() as u8);
+}
+
+// How much red is gained and blue lost (or the other way round) at a temperature of +1 (-1)
+const TEMPERATURE_SCALE: f32 = 0.3;
+
+// Warm (positive) or cool (negative) the image by scaling red and blue in opposite directions,
+// green is left alone as the neutral point
+fn temperature(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, temperature: f32) {
+    let scale = |gain: f32| -> Vec<u8> { (0..=255).map(|c| (c as f32 * gain).round().clamp(0.0, 255.0) as u8).collect() };
+    let red = scale(1.0 + temperature * TEMPERATURE_SCALE);
+    let blue = scale(1.0 - temperature * TEMPERATURE_SCALE);
+    img.par_chunks_mut(3).for_each(|pixel| {
+        pixel[0] = red[pixel[0] as usize];
+        pixel[2] = blue[pixel[2] as usize];
+    });
+}
+
+// Scale the HSV saturation and rotate the hue (in degrees) of every pixel
+fn saturation_hue(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, saturation: f32, hue_shift: f32) {
+    img.par_chunks_mut(3).for_each(|pixel| {
+        let [hue, s, v] = rgb_to_hsv(&Rgb([pixel[0], pixel[1], pixel[2]]));
+        pixel.copy_from_slice(&hsv_to_rgb([hue + hue_shift, s * saturation, v]).0);
+    });
+}
+
+// Standard sepia tone matrix, clamped to the channel range
+fn sepia(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>) {
+    img.par_chunks_mut(3).for_each(|pixel| {
+        let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|c| c as f32);
+        let toned = [
+            0.393 * r + 0.769 * g + 0.189 * b,
+            0.349 * r + 0.686 * g + 0.168 * b,
+            0.272 * r + 0.534 * g + 0.131 * b,
+        ];
+        pixel.copy_from_slice(&toned.map(|c| c.round().min(255.0) as u8));
+    });
+}
+
+// Most `sharpen` is clamped to, beyond this the mask only amplifies noise
+pub const MAX_SHARPEN: f32 = 4.0;
+
+// Unsharp mask on luminance: each pixel's luminance moves `amount` times its difference from a 3x3
+// Gaussian blur away from it. The same offset goes on all three channels, which changes Y and leaves
+// Cb and Cr alone, so edges get crisper without colored fringes. The result is held within the
+// luminance range of the pixel's 3x3 neighbourhood so strong amounts can't ring into halos.
+pub fn sharpen(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, amount: f32) {
+    let (width, height) = img.dimensions();
+    if width == 0 {
+        return;
+    }
+    let luma: Vec<f32> = img.pixels().map(crate::luminance).collect();
+    let at = |x: i64, y: i64| luma[(y.clamp(0, height as i64 - 1) as u32 * width + x.clamp(0, width as i64 - 1) as u32) as usize];
+    let weights = [1.0, 2.0, 1.0];
+    
+    img.par_chunks_mut(width as usize * 3).enumerate().for_each(|(y, row)| {
+        for (x, pixel) in row.chunks_mut(3).enumerate() {
+            let (x, y) = (x as i64, y as i64);
+            let (mut blur, mut low, mut high) = (0.0, f32::MAX, f32::MIN);
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let l = at(x + dx, y + dy);
+                    blur += l * weights[(dx + 1) as usize] * weights[(dy + 1) as usize] / 16.0;
+                    low = low.min(l);
+                    high = high.max(l);
+                }
+            }
+            let l = at(x, y);
+            let offset = (l + (l - blur) * amount).clamp(low, high) - l;
+            for c in pixel.iter_mut() {
+                *c = (*c as f32 + offset).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    });
+}
+
+// Replace every channel value through a 256-entry lookup table built from `f`
+fn map_channels(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, f: impl Fn(u8) -> u8) {
+    let table: Vec<u8> = (0..=255).map(f).collect();
+    img.par_iter_mut().for_each(|c| *c = table[*c as usize]);
+}
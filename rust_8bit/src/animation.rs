@@ -0,0 +1,53 @@
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, DynamicImage, ImageFormat};
+use pyo3::prelude::*;
+use std::io::Cursor;
+
+// Decode frame `frame_index` of an image. Frames of animated GIF, WebP and PNG (APNG) files are
+// composited onto the full canvas, so later frames that only redraw part of it come out whole.
+// Everything else has a single frame and is decoded as usual, see `cmyk::load` for CMYK JPEGs.
+pub fn load(image_data: &[u8], frame_index: u32) -> PyResult<DynamicImage> {
+    let to_err = |e: image::ImageError| PyErr::new::<crate::errors::DecodeError, _>(format!("Failed to load image: {}", e));
+    
+    let frames = match image::guess_format(image_data) {
+        Ok(ImageFormat::Gif) => Some(GifDecoder::new(Cursor::new(image_data)).map_err(to_err)?.into_frames()),
+        Ok(ImageFormat::WebP) => {
+            let decoder = WebPDecoder::new(Cursor::new(image_data)).map_err(to_err)?;
+            decoder.has_animation().then(|| decoder.into_frames())
+        }
+        Ok(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(Cursor::new(image_data)).map_err(to_err)?;
+            decoder.is_apng().then(|| decoder.apng().into_frames())
+        }
+        _ => None,
+    };
+    
+    let Some(frames) = frames else {
+        let img = match crate::cmyk::load(image_data) {
+            Some(img) => img?,
+            None => image::load_from_memory(image_data).map_err(to_err)?,
+        };
+        if frame_index > 0 {
+            return Err(out_of_range(frame_index, 1));
+        }
+        return Ok(img);
+    };
+    
+    // Frames are drawn on top of each other, so all the ones before the wanted frame are decoded too
+    let mut count = 0;
+    for frame in frames {
+        let frame = frame.map_err(to_err)?;
+        if count == frame_index {
+            return Ok(DynamicImage::ImageRgba8(frame.into_buffer()));
+        }
+        count += 1;
+    }
+    Err(out_of_range(frame_index, count))
+}
+
+fn out_of_range(frame_index: u32, count: u32) -> PyErr {
+    let frames = if count == 1 { "frame" } else { "frames" };
+    PyErr::new::<crate::errors::DecodeError, _>(format!("frame_index {} is out of range, the image has {} {}", frame_index, count, frames))
+}
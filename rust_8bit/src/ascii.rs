@@ -0,0 +1,56 @@
+use image::{ImageBuffer, Rgb};
+use crate::pixelate::{self, TileShape, Tiling};
+
+// Characters from dark to light used when no ramp is given
+pub const DEFAULT_RAMP: &str = " .:-=+*#%@";
+
+// Character cells in a terminal are about twice as tall as they are wide, so each character covers
+// a block this many times taller than its width to keep the picture's proportions
+const CELL_ASPECT: u32 = 2;
+
+// Size a `width` x `height` image is stretched to for `cols` characters per line: a whole number of
+// blocks across, so the lines come out exactly `cols` wide. None when it doesn't fit in a u32.
+pub fn stretched_size(width: u32, height: u32, cols: u32) -> Option<(u32, u32)> {
+    if width == 0 || height == 0 {
+        return Some((0, 0));
+    }
+    let stretched_width = width.div_ceil(cols).checked_mul(cols)?;
+    let stretched_height = ((height as u64 * stretched_width as u64) / width as u64).max(1);
+    Some((stretched_width, u32::try_from(stretched_height).ok()?))
+}
+
+// Render `img` as `cols` characters per line, each standing for the average luminance of its block
+// picked from `ramp` (dark to light). Lines are separated by newlines, without a trailing one.
+// Callers bound `cols` with `stretched_size` first, an image that can't be stretched renders empty.
+pub fn render(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, cols: u32, ramp: &[char]) -> String {
+    let (width, height) = img.dimensions();
+    let Some((stretched_width, stretched_height)) = stretched_size(width, height, cols).filter(|&(w, _)| w > 0) else {
+        return String::new();
+    };
+    
+    let block = stretched_width / cols;
+    let stretched = image::imageops::resize(img, stretched_width, stretched_height, image::imageops::FilterType::Nearest);
+    let tiling = Tiling { width: block, height: block * CELL_ASPECT, shape: TileShape::Rectangle };
+    let small = pixelate::downsample(&stretched, tiling, false);
+    
+    let levels = (ramp.len() - 1) as f32;
+    let lines: Vec<String> = small.rows()
+        .map(|row| row.map(|pixel| ramp[(crate::luminance(pixel) / 255.0 * levels).round() as usize]).collect())
+        .collect();
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn stretched_size_fits_whole_blocks() {
+        assert_eq!(stretched_size(100, 50, 30), Some((120, 60)));
+        assert_eq!(stretched_size(10, 10, 40), Some((40, 40)));
+        assert_eq!(stretched_size(2, 1, 3_000_000_000), Some((3_000_000_000, 1_500_000_000)));
+        // Whole blocks wider than a u32, or a height past it
+        assert_eq!(stretched_size(u32::MAX, 1, u32::MAX - 1), None);
+        assert_eq!(stretched_size(1, 2, 3_000_000_000), None);
+    }
+}
@@ -0,0 +1,266 @@
+// Blue-noise threshold matrix for ordered dithering
+
+// Width and height of `BLUE_NOISE`, the matrix tiles seamlessly in both directions
+pub const SIZE: u32 = 64;
+
+// A 64 x 64 permutation of 0..4096 made with void-and-cluster (Ulichney 1993): every threshold sits
+// as far as possible from those just below it, so any level comes out as evenly spread dots with no
+// visible grid. Generated once with a Gaussian filter of sigma 1.5 on a torus, stored row by row.
+pub const BLUE_NOISE: [u16; (SIZE * SIZE) as usize] = [
+     899, 2324,  246, 2657, 1118, 3014,  891, 1394, 2245, 1153, 2501,  640,   54, 1521, 2352, 1827,
+    3671, 1141, 1740, 2369, 2801, 3550, 2206, 1760,  664, 1460, 3261,  349,  790, 3050, 3875,  726,
+    1675, 2573, 3635, 2335, 1021, 3356, 2070, 4016,  964, 2531, 2012, 3214,  227, 4058, 3315, 2640,
+      57, 3861, 1049,  496, 1260, 4062,   40, 1541, 2191, 2955, 1333, 2623, 2098,  665, 2958, 1990,
+    3675, 1796, 3329, 1524, 3705,   30, 2608, 3159, 3949,  248, 3482, 2066, 2969, 3919,  353, 2842,
+     682, 2551,  425,  908, 3780, 1562,  298, 3902, 3109, 2010, 4067, 1690, 2564, 2044, 1243, 2777,
+    3152,  929, 2009, 1619, 3864,   72, 1508, 2837,  265, 1274, 3865, 2665, 1108, 1654,  535, 1435,
+    2997, 1602, 3531, 3168, 2124, 2796,  977, 3714,  725, 3376,  497, 3667,  969, 1676, 4061,  103,
+    3065,  533, 2843,  827, 1935, 3488, 1630,  384, 1916, 1018, 3064, 1309, 2644,  928, 3499, 1255,
+    2068, 3908, 3320, 1857, 3173,  571, 2619, 1095, 2334,  404, 2889,  994, 3773,  178, 2361,  488,
+    3991,  216, 3375,  530, 3080, 2550,  820, 3568, 3123, 1879,  481,  812, 3543, 1951, 2504, 3728,
+     913, 2404, 1873,  204, 1476,  603, 3072, 1917, 2548, 1110, 1868, 2833, 3239,  402, 2605, 1443,
+    2438, 1181, 3994, 2269,  434, 2440, 1211, 3347, 2783,  667, 3795, 1806,  510, 2215, 1606, 3169,
+     143, 1444, 2748,  249, 1192, 2045, 3029,  781, 3710, 1323, 2465,  634, 3316, 1385, 3558, 1821,
+    1144, 2459, 1466, 2818, 1190, 2118, 1743,  597, 2272, 3688, 1593, 3032, 2310,  122, 3400, 1233,
+     269, 3952,  710, 2666, 3835, 2346, 3551, 1293,  340, 4014, 2371,  161, 1301, 2227, 3570,  729,
+    3747, 1730,  165, 1389, 3002, 3918,  589, 2178, 3666, 1504, 2358,  226, 3218, 4040,  751, 2423,
+    3743,  943, 2179, 3580, 2488, 4030, 1453, 3422, 1777,  124, 3598, 1860, 2160, 3026,  604, 2879,
+    3692, 2109,  739, 3731,  188, 3907, 3260, 2630, 1111,   95, 2746, 1312, 3968,  737, 2946, 1800,
+    2183, 3103, 1341, 3373,  945, 1661,  126, 2685, 3195, 1563,  656, 3803, 1722, 3366, 1097, 1975,
+     448, 2720, 3277, 3589,  874, 2684, 1823, 1072,  316, 2601,  867, 3421, 1236, 1963, 2937,  429,
+    1789, 3053,  582, 1639,  849,    3, 2896,  455, 2177, 2673, 3174, 1076,  344, 2547,  906, 1540,
+      92, 2642, 3448, 1869, 2384,  961,  318, 1454, 4065, 1939, 3310,  357, 2093, 1103, 2626,  489,
+    3648, 2556,  351, 1977, 2944,  527, 2203, 3764,  850, 2047, 3503, 2983,  835, 2740,   51, 3160,
+     920, 2176,  631, 2024, 1610,   85, 3768, 3295, 2954, 1944, 3883, 1573, 2792,   46, 1093, 3614,
+    2570, 1248, 3955, 2815, 3337, 1922, 2391, 1164, 3816,  581, 1446, 3901, 1653, 3388, 4046, 2014,
+    3224, 1062,  370, 1311, 3128, 1641, 3492, 2933,  543, 2437,  921, 3748, 1621, 3523, 3164, 1437,
+     815, 1695, 1107, 4042, 2412, 3498, 1201, 1735, 2855,  186, 1352, 2490,  373, 2120, 4013, 1550,
+    3003, 3790, 1169, 2555, 3161, 1275, 2323,  660, 1381,  185, 3073,  549, 2426, 3815, 2249, 1525,
+    3364,  120, 2315,  418, 1370, 3870,  933, 3155, 1632, 2844,  865, 2602,   36, 2292, 1277,  460,
+    3766, 1691, 2928, 4017,  648, 2724, 2190,  845, 3628, 1559, 3097, 2663,  620, 2376,  304, 2041,
+    3871, 2856, 3235,   64, 1505,  754, 3112,  372, 3986, 2309, 3288, 1070, 1804, 3600, 1196, 2387,
+    3479, 1780,  176, 4089,  515, 3555, 2864, 1727, 3976, 2171, 1147, 3597, 1795,  892, 3256,  647,
+    2029,  990, 3138, 1746,  706, 2598,  193, 3539, 2053,  272, 3332, 1803, 3613,  697, 3133, 2758,
+    2258,  762, 2496, 2072,   25, 3721, 1221, 1893,  213, 2240, 1268,   43, 1851, 3948, 1000, 3460,
+     184, 2295,  684, 1918, 2707, 3700, 2057, 2552,  947, 1533,  495, 3859, 2651,  712, 2846,  296,
+     612, 1362, 2337, 2795, 1037, 1924,  321,  944, 2518, 3513,  734, 2653,  339, 1377, 2756,  274,
+    3928, 2637, 3670, 2128, 3468, 2913, 2235, 1281,  675, 4027, 2316, 1099, 2895, 1968, 1035, 1523,
+     152, 3573, 1163, 3415, 1518, 2546,  409, 2898, 3324, 3917,  690, 3553, 2779, 1345, 2973, 1659,
+    2638, 1314, 3800, 3338, 1041,  283, 1359, 3450, 1887, 3679, 3030, 2082,  125, 3229, 1488, 2000,
+    2571, 3196,  811, 3431, 1565, 2244, 3752, 3153,   16, 1537, 2922, 1904, 4081, 3082, 1957, 3500,
+    1666, 1290,  311,  873, 1500,  378, 3762, 1724, 2510, 3018, 1489,  440, 3825,  266, 3312, 3944,
+    1824, 3061,  492, 1882,  787, 3248, 3985, 1663,  986, 2471, 2003, 3226,  864, 2285,  514, 3301,
+     758, 2121,  376, 1547, 2383, 3877, 2862,  663,    4, 2705,  740, 1225, 1704, 3495, 1014, 3947,
+      29, 3693, 2090,  257, 2999,  686, 2694, 1355, 2064, 3350,  442, 1217, 2385,   83,  999, 2305,
+     756, 3000, 2429, 4037, 3089, 1982, 1005, 3308,   94,  927, 3571, 2175, 1320, 2672, 2307,  839,
+    2595, 1383, 3894, 2812, 2314, 1113, 2038,  618, 2768, 1427,  367, 1674, 3855,  163, 1952, 4071,
+    1116, 3650, 2772, 3075,  613, 1792, 2157, 1139, 3240, 1442, 2230, 4069, 2558,  337, 2298, 2919,
+    1572, 1115, 1772, 3932, 1191, 3530,  427, 4023, 1031, 2444, 3809,  816, 3466, 1455, 3724, 3212,
+     415, 3583, 1848,   42, 1150, 2574,  560, 2832, 3931, 1945, 2745,  731, 3116, 1699,  556, 3461,
+     244, 2091, 1009,  127, 3582,  348, 3087, 3661,   79, 3801, 2979, 1119, 2587, 3114, 1431, 2831,
+    2457,   37, 1874,  950, 3511,  177, 2984, 3950, 2410, 3525,  256, 2994,  924, 3742, 1850,  720,
+    2250, 3118,  529, 2596, 2288, 1503, 1859, 2576,  276, 1739, 3092, 2165, 2863,  555, 2641, 1808,
+    1198, 1531, 2841, 2267, 3396, 3819, 1638, 2193, 1256,  314, 1627, 3361,   60, 4057, 2015, 1205,
+    3031, 3674, 2443, 3228, 1769, 1339, 2534, 1592, 2226,  824, 3474, 2048,  642, 3691,  962,  424,
+    1677, 3440, 1342, 3999, 2544, 1478,  802, 1684,  458, 1001, 1608, 1934,  591, 3132, 1189, 3413,
+    4032, 2767,  882, 3348,   98, 3234,  769, 2934, 3631,  680, 1336,  205, 1609, 2032, 3921,  171,
+    2542, 3853,  547,  837, 1413,  262, 3222,  810, 3472, 2432, 3814, 1155, 2507,  881, 3624, 2780,
+    1655,  446, 1441,  859, 4073, 2884,  775, 3357, 1171, 2648, 1517,  225, 2411, 1767, 3382, 2224,
+    2986,  652, 2061,  477, 2217, 3120, 3616, 2686, 2075, 3783, 2769, 3344, 2336, 1428, 2639,  396,
+     147, 1401, 1941, 3807, 1039, 2077, 3881, 1184, 1997, 3383, 2689, 4010, 3220, 1085,  772, 3039,
+    2196, 1006, 3185, 3703, 2113, 2936, 2480,  423, 1520, 2996,  596, 2168, 2952, 1506,  303, 2287,
+     702, 3860, 2715, 2027,  509, 2286,  162, 1967, 3891,  350, 3187, 4021, 2888, 1226,  100, 3930,
+     862, 3701, 2737, 3232, 1066,  356, 1276,  112, 3180,  703, 1324,   58, 3905,  483, 3552, 2030,
+    2486, 3463,  459, 1600, 2967, 2508,  501, 1628,   70, 2281, 1008,  469, 2377, 3617, 1386, 3409,
+     324, 1880, 2681,  119, 1778,  979, 4077, 1940, 3720,  965, 1828,  240, 3487, 1925, 3303, 1044,
+    3136, 1870,    0, 3057, 3694, 1036, 3514, 2956,  637, 1667, 2115,  955,  508, 3254, 1980, 1527,
+    2422, 1213,  136, 1598, 3858, 1890, 2453, 4047, 1807, 2381, 3542, 1042, 2095, 2906, 1720, 1069,
+     643, 2853, 3704, 2304,  271, 1358, 3493, 2711, 3166, 3746, 1482, 1884, 2897,   31, 2129, 2765,
+    1597, 3996, 1193, 2393, 3536,  538, 1329, 2762,   17, 3208, 2634, 3984, 1232,  629, 3906, 2549,
+    1361, 3445, 2255, 1215, 1599, 2616, 1790, 1325, 2390, 3708, 2797, 1372, 2329, 3796, 2708,  317,
+    3060, 1815, 3354, 2579,  711, 3529, 2971,  952, 1507,  505, 3012, 2631, 1551,  730, 3722, 3178,
+    2123, 1782, 1166,  773, 3184, 4072,  968, 2153,  576,  869, 2483, 3565,  735, 1726, 3767,  545,
+     884, 3504,  419, 2911, 1574, 3233, 2360, 3590, 1159, 2220, 1584,  801, 2836, 2363,  202, 1755,
+     391,  840, 3992,  563, 3334,  250, 3953,  390, 3307,  836,   15, 3577, 1701,  736, 1087, 3544,
+     615, 4033,  939, 2207, 1354,  493, 2145,  252, 3285, 3828, 1913,  211, 3965, 2311,  286, 1346,
+    3997,   90, 3283, 2698, 1986, 1679,  155, 2924, 1805, 3972,  191, 3108, 1249, 3291, 1047, 2566,
+    3071, 2279, 1395,  755, 3759,  293,  854, 1759,  592, 3410,  355, 3629, 1987, 1447, 3249, 2912,
+    3660, 2701, 1817, 2921, 2407,  916, 2122, 2731, 1208, 2018, 3093, 2599,  352, 2995, 2239, 1485,
+    2607, 2046,  362, 3723, 2752, 3317, 1685, 2604, 1231, 2280,  890, 1403, 3125,  995, 3412, 2603,
+    3001,  935, 1529, 3775,  360, 2418, 3611, 1178, 3379, 1390, 2738, 1969,  431, 2333, 4074, 1791,
+     301, 1983, 3279, 2650, 1846, 2194, 3066, 2610, 4003, 1350, 2445, 3104,   81, 3827,  785, 1165,
+    2056, 1459,  151, 1112, 3638, 1477, 3106, 3777,  558, 1567, 4063, 1132, 1853, 3406, 3937,  111,
+    3267, 1235, 2925, 1636,    5, 1082, 3942,  723, 2878, 3454,  449, 3607, 2730, 2002, 1672,  513,
+    2332, 3545, 2541,  602, 1267, 3090,  714, 2582,  411, 2201,  989, 3863, 1579, 2883,  108, 1368,
+    3676,  972, 3938,   50, 1135, 3874, 1420,  134, 1947, 2929,  841, 1702, 1106, 2643, 2263, 3439,
+     580, 3811, 2327, 3186, 1902,  671,   88, 1814, 2477, 3423,  201, 2395,  661, 1330, 2074,  902,
+    1775, 3851,  752, 3433, 2368, 3078, 1855, 3662,  106, 1984, 1612, 2389,  653,   18, 3839, 1229,
+    2040,  258, 1812, 3325, 2137, 3899, 1581, 2020, 3713, 3210,  655, 2420, 3533,  842, 2108, 3219,
+    2739,  617, 1658, 2951, 2476,  609, 3443,  971, 3253,  451, 2150, 3922, 3494,  526, 1575,  241,
+    2511, 2848,  893,  452, 4041, 2533, 3535, 1090, 3010,  870, 2100, 2909, 3824, 2635,  484, 2891,
+    2318,  273, 2561, 1418, 2023,  852,  422, 1410, 2502, 1065, 2978, 4082, 1304, 3328, 2871,  782,
+    3124, 1332, 4036, 1032, 2876,   19,  959, 2799,  275, 1670, 2950,  169, 1412, 3313,  532, 2408,
+    1172, 3520, 2289,  375, 3615, 2067, 1596, 2679, 3706, 1259, 2539,  307, 2793, 1999, 3275, 4022,
+    1801, 1322, 3505, 2166, 1228, 2830, 1633, 2192,  426, 3758, 1522,  336, 1011, 3321, 1576, 3690,
+    3165, 1096, 3557,  525, 4055, 2670, 3491, 2094, 3190, 3707,  315,  846, 2170, 2553, 1765, 3734,
+    2396,  696, 2628,  466, 1716, 2348, 3528, 1360, 4029, 1129, 1901, 3642, 2627, 1083, 1836, 3963,
+     228, 2019, 3131, 1272,  877, 3213,  247, 2326,  575, 1776, 3360, 1544,  796, 1305, 2942, 1033,
+     666, 3037,    6, 1708, 3387,  277,  764, 3904, 2592, 1180, 3144, 3653, 1747, 2257,   41, 1254,
+     658, 2127, 2827, 1723, 1185,  140, 2923,  931,  506, 1729, 2741, 3475, 1528,  482, 1048,  150,
+    1605, 3595, 1962, 3207, 3797,  744, 3142,  524, 2308, 3326,  807, 2155,  392, 3770, 2964, 1451,
+    2656,  749, 1583, 4039, 2823, 1856, 3856, 1154, 2803, 4095,    7, 3045, 3769, 2455,  129, 2236,
+    3627, 2006, 3869, 2494,  970, 3005, 1398, 3225, 1715,   66, 1970, 2470,  568, 3062, 3993, 2719,
+    1881, 3885,  224, 3284, 2514, 3657, 1510, 2303, 3981, 1297, 2063,  164, 3217, 3911, 2702, 3404,
+     371, 2917, 1434,  168, 1220, 2759, 1542, 1985, 2678,   84, 3841, 2874, 1603, 2265,    8,  907,
+    3199, 3763,  454, 2524,   71, 1469,  716, 3086, 2005,  909, 2259, 1142, 1946,  590, 3442, 1534,
+    2677,  347, 1452,  646, 3681, 1897, 2372,  491, 3566, 2743,  813, 3395, 1396,  917, 2042,  327,
+    3418, 1404,  984, 2184,  641, 1942,  364, 3298, 2622,  708, 3741, 2466,  903, 1914, 1291, 2151,
+     789, 1074, 3966, 2509, 2101, 3663,  333, 1060, 3194, 1750, 1374,  544, 1012, 3363, 2538, 3560,
+    1731, 2181, 1075, 1930, 3444, 2248, 3669,  332, 1432, 3591,  507, 2646, 3158, 1718, 3964,  843,
+    3262, 1105, 2208, 3156, 2712,  114, 3961, 1057, 2087, 1298, 4044,  215, 2869, 3771, 2585, 1637,
+     803, 2927, 3745, 1564, 3088, 3925,  974, 1833,   34, 3043, 1102, 1681, 2940,  584, 3140, 3808,
+    2786, 2210, 3272,  557,  932, 3342, 2403, 3813,  760, 3484, 2559, 3102, 4060, 1899,  605, 1283,
+     292, 2811, 3651, 3077,  856, 2751, 1202, 2425, 3384, 2838, 1642, 3903,  179,  998, 2525,  285,
+    1923, 2870, 4066,  395, 1264, 1624, 3365, 2824,  650, 3079, 2343, 1622, 2185,  471, 1177, 3183,
+    2290,  502, 2609,   80, 2341, 1237, 2847, 3496, 1421, 2271, 3541,  284, 4038, 2370,   52, 1774,
+    3427,  229, 1546, 2970, 1862,   97, 1650, 2901, 2144,  278, 1128, 2004,  142, 1493, 3009, 3889,
+    2379,  694, 1405,  173, 1683,  522, 4009, 1779,   93,  763, 2331, 1262, 3290, 2105, 2935, 1349,
+    3750,  724, 1694, 3452, 2083,  784, 2312,  259, 1844, 3789,  417,  996, 3678, 1876, 3451,  138,
+    4070, 1751, 3594,  905, 3392,  443, 2081,  778, 3946,  499, 1994, 2718, 1463,  973, 3654, 1219,
+    2436, 1991, 3716, 1251, 2612, 4083, 1182,  503, 1415, 3941, 2350, 3647,  904, 2654, 2111, 1034,
+    3401, 1858, 3958, 2554, 3554, 2148, 2966,  954, 3215, 2051, 3656,  441, 1561, 3715,  500, 3402,
+    2268,   59, 2485,  960, 2660, 3829, 3047, 1175, 3455, 1406, 2493, 3300, 2981,  685, 1475, 2805,
+    1084, 2107, 1285, 2902, 1816, 3802, 2699, 1656, 2489, 3188, 1302,  695, 3419, 2114, 2671,  625,
+    3857,  871,  405, 3462,  669, 2043, 3204, 3592, 2791, 1687,  645, 2857, 3302,  464, 3737,  180,
+    1549, 2872,  478, 1088, 3134, 1315,  235, 2565, 3880, 1146, 3042, 2669,  863, 2448, 1798, 1086,
+    2787, 1440, 3619, 3145,  208, 1501,  523, 2001, 2693,  818, 1764,  116, 1252, 2649, 3823, 2378,
+     586, 3345,  233, 2447,  692, 1384,  400, 3323,  200,  966, 3794, 2985, 1818,  398, 3054, 1647,
+    3163, 2781, 1450, 2355, 3020, 1017,  287, 2392,  809, 3353,  219, 1835, 1157, 1601, 3143, 2497,
+     847, 3276, 2069, 2413,  753, 3810, 2022, 1479,  593, 1698,  146, 1955, 4054,  239, 3137,  771,
+    3876, 1900,  594, 1244, 1847, 3501, 2415, 3915,  189, 3604, 2904, 3982, 2274,  369, 1989,  934,
+    3636, 3006, 1530, 3977, 3146, 2209, 3588, 1133, 2322, 2829, 1703,   82, 2417, 3987, 1371,  148,
+    1050, 1865, 3995,   73, 1680, 3739, 2700, 1867, 1223, 2186, 3848, 2520, 3508, 2266,  691, 1960,
+    3784, 1294,   33, 3512, 1752,  343, 3335, 2868, 3584, 2482, 3306, 1010, 2882, 1473, 3556, 2117,
+     361, 3035, 2367, 4008, 2760,  761, 3198, 1052, 1645,  606, 2035,  949, 3490, 1614, 3273,   26,
+    1711, 2593,  388, 1929, 1028,   53, 2918, 1548, 4090, 2052,  624, 3684, 1183,  799, 3519, 2211,
+    3362, 2581,  738, 3265, 2212,  554, 1436, 3923,   20, 3150, 1467,  894,  322, 4019, 1348, 2920,
+     428, 2683, 4091, 1458, 3040, 2613, 1168,  868, 2197,  412, 1382, 3625,  559, 2328, 1156, 2659,
+    1594, 3435,  137, 1038, 2132,  377, 1429, 2189, 2814, 3330, 2468, 1424,  531, 2704, 1125, 4012,
+    2221, 1257,  819, 3473, 2709, 3830, 1842,  745,  281, 3408, 1425, 2591, 3250, 1915, 2725,  546,
+    1669,  326, 1334, 2867, 1127, 3414, 2982,  766, 3517, 2624,  552, 3038, 1896, 2589,  117, 3251,
+    1117, 1820, 2300,  878,  494, 1979, 3893,   67, 1810, 3998, 2736, 2086, 1707, 3246,   27, 3967,
+     704, 1307, 2886, 1696, 3779, 3011, 3593,  290, 4085, 1195,   68, 3015, 3847, 2182, 3113,  705,
+    2854, 3761, 3223, 2342,  569, 1319, 2162, 3264, 2467,  936, 2931,  407, 2159,  243, 1438, 3933,
+    2414, 3639, 2125, 3778, 1937,  299, 2449, 2017, 1094, 1668, 2142, 3725, 1054, 3397, 1554, 2139,
+    3621,  633, 3368, 2790, 3652, 1373, 2442, 3434, 2968, 1239,  779,  190, 3818,  886, 2839, 1885,
+    2475, 3738, 2073,  636, 2567,  953, 1854, 2386,  800, 1732, 3632, 1959,  872,  253, 1813, 1344,
+     473, 1981,  194, 1100, 1744, 3564,  363, 2785, 1234, 3799, 1719, 3612, 1058, 3785, 3008,  853,
+    3177, 1061, 2723,  135,  918, 4049, 1512,  438, 2875, 3979,  139, 1343, 2804,  393, 3929,  808,
+    2523,  267, 1240, 2065,  160, 3105,  707, 1558,  467, 2357, 3467, 3095, 2519, 1269, 3576,  445,
+     981, 3098,  294, 3374, 1499,   38, 3266, 1326, 2717, 3154,  430, 2577, 1465, 3311, 3711, 2499,
+    3507, 1526, 2800, 4045, 2545, 3069,  919, 3945, 1903,   77, 2340,  722, 2753, 1762, 2313,   32,
+    1891,  600, 1566, 3457, 1787, 2680, 3211, 3672, 2260,  829, 3346, 2473,  632, 2254, 1786, 3121,
+    1462, 3868, 3202, 1623, 3971, 1068, 1911, 2658, 3729,  956, 1495, 1878,  313, 2180, 1556, 3286,
+    2283, 1734, 1227, 4031, 2195, 2806, 3834,  570, 3537, 2173,  748, 3936, 2915, 1045, 2261,  101,
+    3021,  795, 2169,  518, 1365,  110, 2317, 1555,  578, 3515, 3023, 1498, 3377,  453, 1203, 3581,
+    1369, 3927, 2977,  420, 2330, 1134,  579, 1340,  282, 1830, 3091, 1578, 3665, 2914, 1197,   45,
+    2784, 2247,  910, 2560,  550, 2225, 3385,  325, 3175, 2136, 3892,  595, 2881, 4075,  644, 2722,
+      96, 3548, 2907,  876,  475, 1678, 1046, 2008,  170, 1588, 1162, 2354,  345, 1662,  679, 4001,
+    1126, 1761, 3781, 2945, 3417, 2021, 3673, 3227, 2645, 2135, 1151,  214, 2500, 3914, 2106, 2828,
+     255, 2434, 2031,  814, 3838, 3101, 1996, 3485, 2588, 3837, 1148,  197, 1961,  937, 3381, 3786,
+     700, 1831,  380, 3547, 2974, 1363, 3782,  883, 1713,   48, 2572, 1053, 3318, 1784, 1161, 3695,
+    1449,  728, 1866, 2629, 3230, 3645, 2464, 3059, 4000, 2633, 3231, 3477, 1910, 3602, 2688, 2026,
+    3247, 2528,  166,  975, 1660,  659, 1122,  374, 1388,  817, 4020, 3179, 1625,  940,  616, 3258,
+     997, 3526, 1266, 3331, 1651,  308, 2433,  848, 1515, 2816,  693, 2409, 4007,  342, 2513, 2060,
+    1380, 3687, 2860, 1138, 1754,  128, 2430, 2819, 1270, 3007, 3578, 1408, 2388,  154, 3130, 1965,
+    2439, 3849,  305, 2291, 1399,  115,  701, 1299, 1841,  897,  519, 1393,   21, 3058, 1246,  399,
+    1480,  608, 3341, 2241, 2742, 3983, 2515, 2961, 3776, 1809, 2763,  382, 1998, 3683, 2661, 1697,
+    4092,  540, 2625,  109, 2858, 1064, 3685, 3044,   39, 2116, 3518, 2963, 1271, 3259, 1626,  504,
+    3085, 2344,  206, 2084, 4093, 3282,  574, 2007, 3940,  743, 1908,  358, 3845,  861, 2676,  486,
+     926, 3036, 3393, 1081, 3975, 2079, 3476, 2802,  238, 3699, 2146, 2778, 3846,  805, 2198, 3900,
+    2798, 3680, 1912, 1296, 3538,  222, 1472, 2172,    9, 3386,  674, 2325, 1179, 3403,   78, 2301,
+    1875, 3139, 1536, 2243, 3951, 1920,  548, 1745, 4078, 1089,  456, 1552, 2199,  794, 2721, 3897,
+     923, 1263, 3398,  687, 2618,  941, 1615, 3534,  234, 2338, 3441, 2770, 1577, 2218, 3405, 4004,
+    1317, 2138, 1643,  621, 2962, 1706,  985, 2339, 3263, 1570, 3017, 1025, 2460, 1590, 3358,  221,
+    1055, 2347,  797,  385, 3111,  895, 1928, 3209, 1024, 2469, 1347, 3760, 3049,  746, 1461, 2905,
+    1188,  831, 3751,  435, 1337, 3394, 2691, 1284, 2349, 3319, 1895, 3872,  220, 3572, 1861,   75,
+    2451, 3726, 1682, 2993, 1414, 2299, 3067, 1124, 2692, 1487,  987,  520, 3027, 1120,  230, 1773,
+    2578,   22, 2776, 3735,  270, 2580, 3840,  476, 1950,  688, 4079,  288, 1974,  534, 2621, 1839,
+    2991, 1545, 4076, 2675, 1607, 2458, 3916,  626, 3610, 1646, 2899,  198, 1819, 2161, 3852,  379,
+    3446, 2049, 2834, 2487,  798, 2141,  209, 3200,  654, 2775,  885, 2492, 3126, 1136, 2916, 1514,
+    3203,  320, 1988,  490, 3510,   10, 3878,  457, 2092, 3167, 4043, 1825, 3336, 2085, 3682,  747,
+    3172, 3587, 1218, 2033, 1496,  822, 3024, 1391, 3606, 1140, 2615, 1483, 3197, 3788, 1212, 3601,
+     566, 3411,  149, 2071, 3659,  468, 1278, 2820,  263, 2097, 4050,  551, 2652,  958, 3252, 2397,
+     651, 3655,    2, 1737, 3081, 3620, 1026, 3912, 1535,  102, 3744, 1351,  410, 2296,  678, 4048,
+    2130, 1016, 2810, 3956,  823, 1907, 2773, 1756, 3732,  677,   69, 2597, 1306,  335, 2850, 1519,
+    2242,  461,  887, 3236, 2427, 3420,  346, 2188, 2835,   55, 2096, 3437,  866, 2826,   86, 2223,
+     976, 2522, 1321, 3268, 1059, 2989, 2246, 1753, 3458, 1170,  830, 3122, 3527, 1671,  132, 1416,
+    3052, 1587, 1152, 4028,  564, 1448, 2620, 1871, 2270, 2990, 1783, 3389, 2013, 3658, 2632, 1331,
+     539, 3605, 2532, 1186, 2219, 3296, 1313,  898, 2498, 1417, 2251, 3540,  788, 3833, 2398, 1051,
+    3960, 1894, 2866, 3867,  104, 1797, 3989,  946, 3289, 1766, 3910,  583, 2321, 1407, 1758, 3170,
+    3943, 1971, 2865,  681, 1840,   61, 3798,  733, 2479, 2771, 1543, 2222, 1241, 2521, 3970, 2744,
+     331, 2205, 2586, 3297, 2037, 2948,  300,  806, 3559,  541, 1077, 2706,  780, 1648,  130, 3280,
+    2947, 1728,   99, 1553, 3034,  601, 3668,  167, 3459, 3115, 1013, 2939, 1620, 1954,  553, 3430,
+     133, 2540, 1422,  630, 2216, 1250, 2664, 1568,  670, 2474, 1303, 3051,  264, 3842, 2543,  433,
+     793, 1571,  245, 3844, 2380, 3372, 1375, 3162,  439, 3884,  172, 3622,  387,  742, 2036, 1023,
+    3428, 3774,  791,  192, 1101, 2364, 3862, 3147, 1328, 2484, 4034,  242, 3041, 3817, 1063, 1964,
+    2375,  776, 3352, 3843,  338, 2590, 1705, 2817, 2119,  389, 1886, 3988,  199, 2512, 3157, 1318,
+    2965, 1714, 3677, 1007, 3119, 3730,  450, 3004, 3562,  330, 3712,  978, 1921, 2890, 1080, 3630,
+    2131, 2976, 3524, 1200, 2714,  915, 2143, 1692, 1029, 1992, 3205, 1770, 2887, 3756, 3149, 1811,
+     573, 1308, 2789, 1829, 3481,  485, 1589, 1993,  153, 3351, 1629, 2089, 1258, 2273, 3438,  368,
+    3990, 1247, 1898, 2424, 1020, 2054, 4080, 1109, 1484, 3697,  623, 1286, 2774,  963, 3755, 2156,
+     834,  436, 3367, 2695,  291, 1877, 2400, 1121, 2078, 1686, 2713, 2234, 3424,  628, 1616, 3305,
+    1357,  403, 2256, 1768,  511, 4052,  237, 2894, 3449, 2435, 1292,  804, 2278, 1471,  157, 2472,
+    1649, 3237, 2152, 3957, 1356, 2536, 3641,  925, 2807, 2231,  858, 3193,  474, 2584,  715, 1494,
+    2696, 3100,  512, 3686, 1439, 3192,  676,  236, 2953, 2461, 3257, 2213, 3469, 1557,  302, 1826,
+    4068, 2416, 1210, 2080, 1560, 3521,  826, 4025,   91, 3359,  721, 1426,  195, 4059, 2373,    1,
+    2655, 3962,  844, 3426, 3117, 1585, 2568, 3637,  668,   28, 4011, 2647,  498, 3483, 1174, 4086,
+    2998,  105,  980,  383, 2910,  673, 3099, 1238, 3954,  622, 3586, 1423, 3926, 2908, 1710, 3626,
+     144, 2174,  942, 2849,  183, 2667, 3470, 2302, 3854,  911, 1664,   76,  757, 3046, 3563,  607,
+    2761, 3271,   11, 3920,  718, 2788, 1419, 3171, 2516, 1216, 3890, 3084, 1781, 2782,  855, 1936,
+    3094, 1167, 2506,  107, 2055,  750, 1207, 2238, 1474, 1822, 3339, 1019, 3074, 1976, 2757,  857,
+    1852, 3727, 2662, 3416, 1688, 2062,   47, 2421, 1799,  280, 2682, 1883,   63,  983, 2050, 3176,
+    1176, 3822, 1693, 3333, 1956,  860, 1617, 1242, 1927,  334, 2690, 3939, 1872, 2454, 1224, 2253,
+    1492,  988, 1802, 2365, 3013,  232, 2237,  565, 1863, 2873,  394, 2405, 1056, 3532, 1335, 3753,
+     662, 1709, 3793, 1456, 2845, 3909, 3255,  432, 3806, 2749, 2102,  309, 1640, 3882,  279, 2359,
+     536, 1131, 1481, 2264,  832, 3216, 3821, 1470, 3486, 2987, 1114, 2456, 3696, 3327,  416, 2441,
+     759, 2575,  328, 1289, 3973, 2452, 3618,  517, 3355, 3033, 1378, 3596,  993, 2859,  159, 3879,
+    3148,  421, 3757, 1310, 3574, 1071, 3322, 3836, 1511,  912, 2112, 3293,  561, 2011,  295, 3278,
+    2202,  231, 3022,  967, 2366,  329, 1793, 1004, 2975,  768, 1316, 3633, 2557,  709, 1411, 3603,
+    3304, 2517, 3935,  260, 3664, 1160, 2729,  341,  914, 2059, 3243,  727, 1591, 2284, 1379, 4064,
+    1838, 3464, 2938, 2233,  657,   62, 2813, 2126, 1043, 2446,  635, 2167,  406, 3292, 1644,  838,
+    1972, 2808, 2149,  599, 2583, 1665, 2034,  312, 3048, 3717,   49, 1613, 3791, 2992, 2481, 1538,
+    2766, 3585, 1864,  588, 3698, 1288, 2636, 3546, 2351,  182, 3191, 2229, 1092, 3349, 2903, 2133,
+      56, 3056,  713, 1978, 2462,  542, 1843, 2297, 3888,  472, 1338, 3980,  254, 2821,  672, 3019,
+      87, 1486,  922, 3718, 1763, 3096, 1400, 3733,  223, 4053, 1785, 3432, 1280, 2345, 3643, 2617,
+     251, 3436,  948, 3281,   89, 4051,  741, 2732, 1209, 2450, 3456, 2716, 1279,  774, 3913, 1022,
+     528, 1158, 2537, 3343, 2104, 3076,   65, 1953, 1433, 3887, 1738,  516, 3787,  212, 1712,  930,
+    1932, 1245, 1611, 3502, 3016, 1376, 3391, 2900, 1618, 3340, 2764, 2154, 1834, 3549, 1214, 3689,
+    2134, 2727,  463, 3238, 1149, 2505,  792, 1849, 3189, 1502, 2750,   44, 2988, 1889,  537, 1123,
+    4015, 1430, 1748, 2401, 2930, 1367, 3516, 2294, 1736,  611, 1104,  444, 2262, 1794,  181, 2140,
+    3110, 4094, 1490,  306,  828, 1595, 4002,  614, 3369,  938, 2530, 2949, 2025, 1300, 2728, 4026,
+    2356, 3719, 2794,  207,  875, 4018,  113, 1078,  698, 2495,   12, 1027, 3141,  447, 2478, 1721,
+    1015, 3934, 2406, 1949,  297, 4006, 3371,  359, 2353,  689, 1143, 3609,  896, 3873, 1513, 2943,
+    2293,  639, 3709,  386, 1906,  992, 3068,  175, 3772, 3242, 1909, 4035, 2941, 3623, 2594, 3453,
+    1741,   35, 2282, 2932, 3792, 2687, 1130, 2276, 2809,  354, 1265, 3478,  777, 2431, 3241,  627,
+    3390,  414, 1067, 2110, 1733, 2614, 2187, 3754, 1948, 3497, 1516, 3736, 2319,  889, 3399,  187,
+    2840,  619, 1282, 3634, 2893, 1539, 1002, 2103, 2960, 3886, 2535, 2028,  437, 2419, 3370,  118,
+    1931, 3127, 1187, 2755, 3866,  567, 2147, 1582,  901, 2851, 2394,  210, 1491,  957,  562, 1204,
+    2825,  765, 3506, 1261, 1919,  470, 3489, 1725, 3245, 1995, 4056,   23, 1657, 3831,  289, 1468,
+    1845, 2972, 2428, 3850, 3269,  585, 1509, 2957,  365, 1230, 3025,  598, 1700, 4024, 1364, 3055,
+    1905, 3314, 1652,   13, 2252,  577, 2754, 3567, 1295,  203, 1604, 3287, 2861, 1327, 1788,  786,
+    3575, 2526,  217, 3447, 1445, 2600, 3309, 3959,  408, 1402, 3561,  732, 3151, 2088, 3270, 1580,
+    3765, 2039, 2606,  366, 3182, 1003, 2491,  141,  783, 1464, 2228, 3129, 1073, 2885, 2163,  991,
+    3978,  767, 1397,  323, 2822, 1194, 3640,  825, 2374, 3974, 2099, 2710,  261, 2529, 2076,  487,
+    3826,  900, 2563, 3509, 1079, 3895, 1689,  401, 1926, 3063,  951,  587, 4005,  310, 2703, 3812,
+    1040, 1569, 2058,  833, 2277,  131, 1145, 1888, 2726, 2204, 1098, 1832, 2611, 3896,  145, 2463,
+     413,  982, 3969, 1635, 2200, 3702, 1409, 3924, 2852, 3569,  480, 2674, 1892,  610, 3649, 2562,
+     397, 3135, 2016, 3429, 1673,  196, 2527, 3244, 1717,  156,  880, 3294, 1206, 3608,  719, 2697,
+    1497, 2164,  381, 2959, 1938, 3206, 2503,  851, 3820, 2306, 3644, 1837, 2214, 1091, 3221, 2320,
+     479, 2892, 4087, 3181, 1771, 3740, 3028,  770, 3646,   14, 2980, 3749,  465, 1222, 1749, 3599,
+    3070, 1392, 3299,  174,  821, 2926,  572, 1973, 1137, 2399,  888, 3425, 1387, 3201,  218, 1634,
+    2734, 1273, 3804,  683, 2232, 4084, 1958,  521, 3579, 2877, 1586, 3805, 1933, 3107, 1030, 3407,
+     121, 3083, 4088, 1366,  717,  158, 1253, 3378, 2733,   74, 1199, 2668, 3522, 1532,   24, 1966,
+    3465, 1287,  268,  638, 2747, 1353,  462, 2402, 1631, 3380,  649, 1457, 2275, 2880,  879, 2158,
+     699, 1943, 2735, 2362, 3480, 1742, 2569, 3274,  319, 3832, 1757,  123, 3898, 2382, 1173, 3471,
+];
@@ -0,0 +1,102 @@
+use image::{DynamicImage, ImageBuffer, Rgb};
+use pyo3::prelude::*;
+use std::io::Cursor;
+
+// JPEG markers: Adobe's APP14 segment, start of scan and end of image
+const APP14: u8 = 0xee;
+const SOS: u8 = 0xda;
+const EOI: u8 = 0xd9;
+
+// Decode a CMYK JPEG that has no Adobe marker, or return None to decode the image as usual.
+//
+// Photoshop and most other software write CMYK JPEGs with inverted samples and an Adobe APP14
+// segment saying so, and the regular JPEG decoder assumes that for every CMYK file. Files without
+// the segment store plain ink amounts, which that assumption turns into a color-shifted negative.
+pub fn load(image_data: &[u8]) -> Option<PyResult<DynamicImage>> {
+    if image::guess_format(image_data).ok()? != image::ImageFormat::Jpeg || !plain_cmyk(image_data) {
+        return None;
+    }
+    
+    let to_err = |e: jpeg_decoder::Error| PyErr::new::<crate::errors::DecodeError, _>(format!("Failed to load image: {}", e));
+    let mut decoder = jpeg_decoder::Decoder::new(Cursor::new(image_data));
+    let decoded = decoder.decode().map_err(to_err).and_then(|pixels| {
+        let info = decoder.info().filter(|info| info.pixel_format == jpeg_decoder::PixelFormat::CMYK32);
+        let info = info.ok_or_else(|| PyErr::new::<crate::errors::DecodeError, _>("Failed to load image: expected CMYK samples"))?;
+        // The decoder hands back 255 minus each stored sample, i.e. how much of each channel is left
+        // uncovered, so the ink and black coverages multiply straight into RGB
+        let rgb: Vec<u8> = pixels.chunks_exact(4)
+            .flat_map(|p| {
+                let k = p[3] as u32;
+                [0, 1, 2].map(|c| ((p[c] as u32 * k + 127) / 255) as u8)
+            })
+            .collect();
+        let buffer: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::from_raw(info.width as u32, info.height as u32, rgb)
+            .ok_or_else(|| PyErr::new::<crate::errors::DecodeError, _>("Failed to load image: truncated CMYK data"))?;
+        Ok(DynamicImage::ImageRgb8(buffer))
+    });
+    Some(decoded)
+}
+
+// Whether the JPEG's frame has four components and no Adobe segment comes before it
+fn plain_cmyk(data: &[u8]) -> bool {
+    let mut adobe = false;
+    let mut i = 2;
+    while i + 4 <= data.len() && data[i] == 0xff {
+        let marker = data[i + 1];
+        if marker == SOS || marker == EOI {
+            break;
+        }
+        let length = u16::from_be_bytes([data[i + 2], data[i + 3]]) as usize;
+        let start = (i + 4).min(data.len());
+        let segment = &data[start..(i + 2 + length).clamp(start, data.len())];
+        match marker {
+            APP14 => adobe |= segment.starts_with(b"Adobe"),
+            // Start of frame markers, 0xc4, 0xc8 and 0xcc in that range are something else.
+            // The segment holds the precision, height and width before the component count.
+            0xc0..=0xcf if !matches!(marker, 0xc4 | 0xc8 | 0xcc) => return !adobe && segment.get(5) == Some(&4),
+            _ => {}
+        }
+        i += 2 + length;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageEncoder, Luma};
+    
+    // 32x8, four 8x8 blocks of flat ink: none, full cyan, a mix and half black. The Adobe file stores
+    // the same inks inverted, behind an APP14 segment.
+    const PLAIN: &[u8] = include_bytes!("../tests/fixtures/cmyk_plain.jpg");
+    const ADOBE: &[u8] = include_bytes!("../tests/fixtures/cmyk_adobe.jpg");
+    // What the blocks look like, each channel uncovered by its ink and by black
+    const EXPECTED: [[u8; 3]; 4] = [[255, 255, 255], [0, 255, 255], [190, 137, 49], [122, 122, 122]];
+    
+    fn decode(image_data: &[u8]) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        crate::decode(image_data, &crate::Options::from_kwargs(false, None).ok().unwrap()).ok().unwrap().rgb
+    }
+    
+    #[test]
+    fn cmyk_converts_to_rgb() {
+        assert!(plain_cmyk(PLAIN) && !plain_cmyk(ADOBE));
+        for data in [PLAIN, ADOBE] {
+            let rgb = decode(data);
+            assert_eq!(rgb.dimensions(), (32, 8));
+            for (block, expected) in EXPECTED.iter().enumerate() {
+                let pixel = rgb.get_pixel(block as u32 * 8 + 3, 3);
+                assert!((0..3).all(|c| pixel[c].abs_diff(expected[c]) <= 2), "block {}: {:?}", block, pixel.0);
+            }
+        }
+    }
+    
+    #[test]
+    fn grayscale_promotes_to_equal_channels() {
+        let grey = ImageBuffer::from_fn(20, 12, |x, y| Luma([((x * 9 + y * 5) % 256) as u8]));
+        let mut png = Vec::new();
+        image::codecs::png::PngEncoder::new(&mut png).write_image(grey.as_raw(), 20, 12, image::ColorType::L8).unwrap();
+        assert!(load(&png).is_none());
+        let rgb = decode(&png);
+        assert!(rgb.enumerate_pixels().all(|(x, y, pixel)| pixel.0 == [grey.get_pixel(x, y)[0]; 3]));
+    }
+}
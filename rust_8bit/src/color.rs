@@ -0,0 +1,511 @@
+use image::Rgb;
+use pyo3::prelude::*;
+use rayon::prelude::*;
+use crate::kdtree::KdTree;
+
+// How the difference between two colors is measured when matching pixels to the palette
+#[derive(Clone, Copy, PartialEq)]
+pub enum DistanceMetric {
+    Rgb,
+    Lab,
+    Oklab,
+    Redmean,
+    // Weighted hue, saturation and value differences, see `hsv_distance`
+    Hsv([f32; 3]),
+    // Euclidean YCbCr with the chroma differences divided by the factor, see `rgb_to_ycbcr`
+    YCbCr(f32),
+    // Euclidean RGB in linear light, used in place of `Rgb` when `linear_rgb` is set rather than selected by name
+    LinearRgb,
+}
+
+// Names accepted by `distance_metric`, in the order they are listed in error messages
+pub const DISTANCE_METRICS: &[(&str, DistanceMetric)] = &[
+    ("rgb", DistanceMetric::Rgb),
+    ("lab", DistanceMetric::Lab),
+    ("oklab", DistanceMetric::Oklab),
+    ("redmean", DistanceMetric::Redmean),
+    ("hsv", DistanceMetric::Hsv(DEFAULT_HSV_WEIGHTS)),
+    ("ycbcr", DistanceMetric::YCbCr(DEFAULT_CHROMA_REDUCTION)),
+];
+
+// Default `hsv_weights`: a 45 degree hue shift at full saturation costs as much as black to white
+pub const DEFAULT_HSV_WEIGHTS: [f32; 3] = [16.0, 1.0, 1.0];
+
+// Default `chroma_reduction`: a chroma difference counts half as much as the same luminance difference
+pub const DEFAULT_CHROMA_REDUCTION: f32 = 2.0;
+
+impl DistanceMetric {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        DISTANCE_METRICS.iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|&(_, metric)| metric)
+            .ok_or_else(|| {
+                let valid: Vec<&str> = DISTANCE_METRICS.iter().map(|(candidate, _)| *candidate).collect();
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown distance metric '{}', expected one of: {}", name, valid.join(", ")))
+            })
+    }
+}
+
+// Fixed bits-per-channel pixel formats of old display hardware
+#[derive(Clone, Copy, PartialEq)]
+pub enum BitLayout {
+    Rgb332,
+    Rgb565,
+    Rgb444,
+    Rgb222,
+}
+
+// Names accepted by `bit_layout`, in the order they are listed in error messages
+pub const BIT_LAYOUTS: &[(&str, BitLayout)] = &[
+    ("rgb332", BitLayout::Rgb332),
+    ("rgb565", BitLayout::Rgb565),
+    ("rgb444", BitLayout::Rgb444),
+    ("rgb222", BitLayout::Rgb222),
+];
+
+impl BitLayout {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        BIT_LAYOUTS.iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|&(_, layout)| layout)
+            .ok_or_else(|| {
+                let valid: Vec<&str> = BIT_LAYOUTS.iter().map(|(candidate, _)| *candidate).collect();
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown bit layout '{}', expected one of: {}", name, valid.join(", ")))
+            })
+    }
+    
+    // Bits stored for red, green and blue
+    pub fn bits(self) -> [u32; 3] {
+        match self {
+            BitLayout::Rgb332 => [3, 3, 2],
+            BitLayout::Rgb565 => [5, 6, 5],
+            BitLayout::Rgb444 => [4, 4, 4],
+            BitLayout::Rgb222 => [2, 2, 2],
+        }
+    }
+}
+
+// Decode an sRGB channel value to linear light in 0.0-1.0
+pub fn srgb_to_linear(c: u8) -> f32 {
+    srgb_fraction_to_linear(c as f32 / 255.0)
+}
+
+// Same as `srgb_to_linear` for an sRGB value already in 0.0-1.0, keeping any precision below 8 bits
+pub fn srgb_fraction_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Encode a linear light value in 0.0-1.0 back to an sRGB channel value
+pub fn linear_to_srgb(c: f32) -> u8 {
+    let c = c.clamp(0.0, 1.0);
+    let encoded = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
+
+// An sRGB color in linear light, scaled to 0.0-255.0 so it is comparable with plain RGB values
+pub fn rgb_to_linear(pixel: &Rgb<u8>) -> [f32; 3] {
+    pixel.0.map(|c| srgb_to_linear(c) * 255.0)
+}
+
+// Convert a color to hue (degrees, 0-360), saturation and value (both 0.0-1.0)
+pub fn rgb_to_hsv(pixel: &Rgb<u8>) -> [f32; 3] {
+    let [r, g, b] = pixel.0.map(|c| c as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    
+    [hue, saturation, max]
+}
+
+// Inverse of `rgb_to_hsv`, saturation and value are clamped to 0.0-1.0 and hue wraps around
+pub fn hsv_to_rgb(hsv: [f32; 3]) -> Rgb<u8> {
+    let hue = hsv[0].rem_euclid(360.0);
+    let saturation = hsv[1].clamp(0.0, 1.0);
+    let value = hsv[2].clamp(0.0, 1.0);
+    
+    let chroma = value * saturation;
+    let x = chroma * (1.0 - ((hue / 60.0).rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match (hue / 60.0) as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = value - chroma;
+    Rgb([r, g, b].map(|c| ((c + m) * 255.0).round() as u8))
+}
+
+// Convert an sRGB color to CIELAB (D65 white point)
+pub fn rgb_to_lab(pixel: &Rgb<u8>) -> [f32; 3] {
+    let r = srgb_to_linear(pixel[0]);
+    let g = srgb_to_linear(pixel[1]);
+    let b = srgb_to_linear(pixel[2]);
+    
+    // Linear sRGB to XYZ, normalized by the reference white
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    
+    let f = |t: f32| {
+        const DELTA: f32 = 6.0 / 29.0;
+        if t > DELTA * DELTA * DELTA {
+            t.cbrt()
+        } else {
+            t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+// Convert an sRGB color to Oklab, lightness runs 0.0-1.0
+pub fn rgb_to_oklab(pixel: &Rgb<u8>) -> [f32; 3] {
+    let r = srgb_to_linear(pixel[0]);
+    let g = srgb_to_linear(pixel[1]);
+    let b = srgb_to_linear(pixel[2]);
+    
+    // Linear sRGB to LMS cone responses, then a cube root and the opponent color matrix
+    let l = (0.412_221_47 * r + 0.536_332_55 * g + 0.051_445_99 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+    
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+// Convert to full-range YCbCr (ITU-R BT.601, as in JPEG) with Cb and Cr divided by `chroma_reduction`.
+// Nearest colors in this space keep the luminance of the source closer than its hue and saturation,
+// the trade JPEG and video make by storing chroma coarser, so shading and edges stay sharp at few colors.
+pub fn rgb_to_ycbcr(pixel: &Rgb<u8>, chroma_reduction: f32) -> [f32; 3] {
+    let [r, g, b] = pixel.0.map(|c| c as f32);
+    [
+        0.299 * r + 0.587 * g + 0.114 * b,
+        (-0.168_736 * r - 0.331_264 * g + 0.5 * b) / chroma_reduction,
+        (0.5 * r - 0.418_688 * g - 0.081_312 * b) / chroma_reduction,
+    ]
+}
+
+// "Redmean" weighted RGB distance: channel weights shift with the average red level,
+// which tracks perception much better than plain Euclidean at almost the same cost
+pub fn redmean_distance(c1: &Rgb<u8>, c2: &Rgb<u8>) -> f32 {
+    let mean_r = (c1[0] as f32 + c2[0] as f32) / 2.0;
+    let dr = c1[0] as f32 - c2[0] as f32;
+    let dg = c1[1] as f32 - c2[1] as f32;
+    let db = c1[2] as f32 - c2[2] as f32;
+    
+    (2.0 + mean_r / 256.0) * dr * dr + 4.0 * dg * dg + (2.0 + (255.0 - mean_r) / 256.0) * db * db
+}
+
+// Weighted distance between two `rgb_to_hsv` colors. Hue wraps around and is scaled to 0.0-1.0
+// like the other components, then damped by the mean saturation since the hue of a grey means nothing.
+pub fn hsv_distance(a: &[f32; 3], b: &[f32; 3], weights: &[f32; 3]) -> f32 {
+    let hue = (a[0] - b[0]).abs();
+    let dh = hue.min(360.0 - hue) / 180.0 * (a[1] + b[1]) / 2.0;
+    let ds = a[1] - b[1];
+    let dv = a[2] - b[2];
+    weights[0] * dh * dh + weights[1] * ds * ds + weights[2] * dv * dv
+}
+
+fn squared_distance(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    let d0 = a[0] - b[0];
+    let d1 = a[1] - b[1];
+    let d2 = a[2] - b[2];
+    d0 * d0 + d1 * d1 + d2 * d2
+}
+
+// Bits kept per channel when indexing the lookup cache (32 x 32 x 32 cells)
+const CACHE_BITS: u32 = 5;
+
+// Palette colors `mean_spacing` looks at before it starts sampling
+const SPACING_SAMPLE: usize = 1024;
+
+// Matches pixels to their nearest palette color, converting the palette into the
+// metric's color space once up front rather than for every pixel
+pub struct ColorMatcher {
+    palette: Vec<Rgb<u8>>,
+    metric: DistanceMetric,
+    converted: Vec<[f32; 3]>,
+    // Nearest palette index for every cell of a reduced-resolution RGB cube, see `with_cache`
+    cache: Option<Vec<usize>>,
+    // Tree over the palette in the metric's color space, see `with_kd_tree`
+    tree: Option<KdTree>,
+    // Set for palettes whose nearest entry can be computed without a search
+    shortcut: Option<Shortcut>,
+    // Palette laid out for the vectorized RGB search, with the "simd" feature and the RGB metric
+    #[cfg(feature = "simd")]
+    packed: Option<crate::simd::PackedPalette>,
+}
+
+// Direct ways of picking a palette entry, see `posterize` and `threshold`
+enum Shortcut {
+    // Levels per channel (red, green, blue) of a uniform RGB grid
+    Grid([usize; 3]),
+    // Luminance above which the second of two colors is picked
+    Threshold(u8),
+}
+
+impl ColorMatcher {
+    pub fn new(palette: Vec<Rgb<u8>>, metric: DistanceMetric) -> Self {
+        let converted = match metric {
+            DistanceMetric::Rgb => Vec::new(),
+            DistanceMetric::Lab => palette.iter().map(rgb_to_lab).collect(),
+            DistanceMetric::Oklab => palette.iter().map(rgb_to_oklab).collect(),
+            DistanceMetric::Redmean => Vec::new(),
+            DistanceMetric::Hsv(_) => palette.iter().map(rgb_to_hsv).collect(),
+            DistanceMetric::LinearRgb => palette.iter().map(rgb_to_linear).collect(),
+            DistanceMetric::YCbCr(chroma_reduction) => palette.iter().map(|c| rgb_to_ycbcr(c, chroma_reduction)).collect(),
+        };
+        ColorMatcher {
+            #[cfg(feature = "simd")]
+            packed: (metric == DistanceMetric::Rgb).then(|| crate::simd::PackedPalette::new(&palette)),
+            palette,
+            metric,
+            converted,
+            cache: None,
+            tree: None,
+            shortcut: None,
+        }
+    }
+    
+    // Match against every combination of `levels` evenly spaced values per channel. Each channel
+    // is rounded to its nearest level directly, so there is no palette search and the metric is unused.
+    pub fn posterize(levels: usize) -> Self {
+        Self::grid([levels; 3], |i, levels| (i * 255 / (levels - 1)) as u8)
+    }
+    
+    // Match against every color a `layout` pixel can store, rounding each channel to its nearest
+    // level. Stored values widen to 8 bits by repeating their bits, the way display hardware does.
+    pub fn bit_layout(layout: BitLayout) -> Self {
+        Self::grid(layout.bits().map(|bits| 1 << bits), |i, levels| {
+            let bits = levels.trailing_zeros();
+            let mut value = 0;
+            let mut filled = 0;
+            while filled < 8 {
+                value = (value << bits) | i;
+                filled += bits;
+            }
+            (value >> (filled - 8)) as u8
+        })
+    }
+    
+    // Palette of every combination of `levels` values per channel, `level(i, levels)` being the
+    // 8-bit value of level i
+    fn grid(levels: [usize; 3], level: impl Fn(usize, usize) -> u8) -> Self {
+        let mut palette = Vec::with_capacity(levels.iter().product());
+        for r in 0..levels[0] {
+            for g in 0..levels[1] {
+                for b in 0..levels[2] {
+                    palette.push(Rgb([level(r, levels[0]), level(g, levels[1]), level(b, levels[2])]));
+                }
+            }
+        }
+        ColorMatcher { shortcut: Some(Shortcut::Grid(levels)), ..ColorMatcher::new(palette, DistanceMetric::Rgb) }
+    }
+    
+    // Two-tone matching: pixels whose luminance is above `threshold` get `foreground`, the rest `background`
+    pub fn threshold(background: Rgb<u8>, foreground: Rgb<u8>, threshold: u8) -> Self {
+        ColorMatcher { shortcut: Some(Shortcut::Threshold(threshold)), ..ColorMatcher::new(vec![background, foreground], DistanceMetric::Rgb) }
+    }
+    
+    // Search the palette through a k-d tree instead of a linear scan, results are identical.
+    // Redmean and HSV aren't Euclidean distances so they keep using the linear scan.
+    pub fn with_kd_tree(mut self) -> Self {
+        self.tree = match self.metric {
+            DistanceMetric::Rgb => {
+                let points: Vec<[f32; 3]> = self.palette.iter().map(|c| c.0.map(|v| v as f32)).collect();
+                Some(KdTree::new(&points))
+            }
+            DistanceMetric::Lab | DistanceMetric::Oklab | DistanceMetric::LinearRgb | DistanceMetric::YCbCr(_) => Some(KdTree::new(&self.converted)),
+            DistanceMetric::Redmean | DistanceMetric::Hsv(_) => None,
+        };
+        self
+    }
+    
+    // Precompute the nearest color for each cell of a 32x32x32 RGB cube so lookups become a
+    // table index instead of a scan over the whole palette. Each cell is matched at its center,
+    // so a pixel can be off from the exact result by at most half a cell (4 levels per channel).
+    pub fn with_cache(mut self) -> Self {
+        let cells = 1usize << (3 * CACHE_BITS);
+        let half = 1u32 << (7 - CACHE_BITS);
+        let cache: Vec<usize> = (0..cells)
+            .into_par_iter()
+            .map(|cell| {
+                let channel = |shift: u32| ((((cell >> shift) as u32 & ((1 << CACHE_BITS) - 1)) << (8 - CACHE_BITS)) + half) as u8;
+                self.nearest_exact(&Rgb([channel(2 * CACHE_BITS), channel(CACHE_BITS), channel(0)]))
+            })
+            .collect();
+        self.cache = Some(cache);
+        self
+    }
+    
+    pub fn palette(&self) -> &[Rgb<u8>] {
+        &self.palette
+    }
+    
+    // Typical step between neighbouring palette colors: the mean, over the palette, of the largest
+    // channel difference to the nearest other color. Large palettes are estimated from an even
+    // sample of SPACING_SAMPLE colors, each still compared against the whole palette.
+    pub fn mean_spacing(&self) -> f32 {
+        if let Some(Shortcut::Grid(levels)) = self.shortcut {
+            return levels.iter().map(|&l| 255.0 / (l - 1) as f32).sum::<f32>() / 3.0;
+        }
+        let stride = self.palette.len().div_ceil(SPACING_SAMPLE).max(1);
+        let nearest: Vec<f32> = self.palette.iter()
+            .step_by(stride)
+            .filter_map(|color| {
+                self.palette.iter()
+                    .map(|other| (0..3).map(|c| color[c].abs_diff(other[c])).max().unwrap_or(0))
+                    .filter(|&difference| difference > 0)
+                    .min()
+            })
+            .map(|difference| difference as f32)
+            .collect();
+        if nearest.is_empty() {
+            return 0.0;
+        }
+        nearest.iter().sum::<f32>() / nearest.len() as f32
+    }
+    
+    // Position of the nearest color in the palette
+    pub fn nearest_index(&self, pixel: &Rgb<u8>) -> usize {
+        match self.shortcut {
+            Some(Shortcut::Grid(levels)) => {
+                let [r, g, b] = [0, 1, 2].map(|c| (pixel[c] as usize * (levels[c] - 1) + 127) / 255);
+                return (r * levels[1] + g) * levels[2] + b;
+            }
+            Some(Shortcut::Threshold(threshold)) => return (crate::luminance(pixel) > threshold as f32) as usize,
+            None => {}
+        }
+        match &self.cache {
+            Some(cache) => {
+                let shift = 8 - CACHE_BITS;
+                let cell = ((pixel[0] as usize >> shift) << (2 * CACHE_BITS))
+                    | ((pixel[1] as usize >> shift) << CACHE_BITS)
+                    | (pixel[2] as usize >> shift);
+                cache[cell]
+            }
+            None => self.nearest_exact(pixel),
+        }
+    }
+    
+    // Scan the whole palette for the closest color
+    fn nearest_exact(&self, pixel: &Rgb<u8>) -> usize {
+        if let Some(tree) = &self.tree {
+            let point = match self.metric {
+                DistanceMetric::Lab => rgb_to_lab(pixel),
+                DistanceMetric::Oklab => rgb_to_oklab(pixel),
+                DistanceMetric::LinearRgb => rgb_to_linear(pixel),
+                DistanceMetric::YCbCr(chroma_reduction) => rgb_to_ycbcr(pixel, chroma_reduction),
+                _ => pixel.0.map(|v| v as f32),
+            };
+            if let Some(index) = tree.nearest(&point) {
+                return index;
+            }
+        }
+        
+        match self.metric {
+            DistanceMetric::Rgb => {
+                #[cfg(feature = "simd")]
+                if let Some(packed) = &self.packed {
+                    return packed.nearest_index(pixel, &self.palette);
+                }
+                crate::find_nearest_index(pixel, &self.palette)
+            }
+            DistanceMetric::Lab | DistanceMetric::Oklab | DistanceMetric::LinearRgb | DistanceMetric::YCbCr(_) => {
+                let point = match self.metric {
+                    DistanceMetric::Lab => rgb_to_lab(pixel),
+                    DistanceMetric::Oklab => rgb_to_oklab(pixel),
+                    DistanceMetric::YCbCr(chroma_reduction) => rgb_to_ycbcr(pixel, chroma_reduction),
+                    _ => rgb_to_linear(pixel),
+                };
+                (0..self.converted.len())
+                    .min_by(|&a, &b| squared_distance(&point, &self.converted[a]).total_cmp(&squared_distance(&point, &self.converted[b])))
+                    .unwrap_or(0)
+            }
+            DistanceMetric::Redmean => {
+                (0..self.palette.len())
+                    .min_by(|&a, &b| redmean_distance(pixel, &self.palette[a]).total_cmp(&redmean_distance(pixel, &self.palette[b])))
+                    .unwrap_or(0)
+            }
+            DistanceMetric::Hsv(weights) => {
+                let point = rgb_to_hsv(pixel);
+                (0..self.converted.len())
+                    .min_by(|&a, &b| hsv_distance(&point, &self.converted[a], &weights).total_cmp(&hsv_distance(&point, &self.converted[b], &weights)))
+                    .unwrap_or(0)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // `count` random colors, the same for the same `seed`
+    fn random_colors(count: usize, seed: u64) -> Vec<Rgb<u8>> {
+        (0..count as u64).map(|i| {
+            let bits = crate::mix(seed ^ i).to_le_bytes();
+            Rgb([bits[0], bits[1], bits[2]])
+        }).collect()
+    }
+    
+    #[test]
+    fn cache_stays_close_to_exact_search() {
+        let palette = random_colors(64, 1);
+        let exact = ColorMatcher::new(palette.clone(), DistanceMetric::Rgb);
+        let cached = ColorMatcher::new(palette.clone(), DistanceMetric::Rgb).with_cache();
+        // Matching at the cell center instead of the pixel moves it at most 4 levels per channel, which
+        // can cost twice that distance over the exact match
+        let slack = 2.0 * (3.0f32 * 4.0 * 4.0).sqrt();
+        let mut differing = 0;
+        for pixel in random_colors(5000, 2) {
+            let distance = |index: usize| (crate::color_distance(&pixel, &palette[index]) as f32).sqrt();
+            let (best, picked) = (exact.nearest_index(&pixel), cached.nearest_index(&pixel));
+            assert!(distance(picked) <= distance(best) + slack);
+            differing += (best != picked) as usize;
+        }
+        // Almost every pixel still gets the exact answer
+        assert!(differing < 5000 / 10, "{} of 5000 pixels differ", differing);
+    }
+    
+    #[test]
+    fn oklab_bands_less_than_rgb() {
+        // Dark blue to pale yellow, matched against a sparse palette so every color spans a band
+        let gradient: Vec<Rgb<u8>> = (0..=255u32).map(|i| Rgb([i as u8, (i * 3 / 4) as u8, (160 - i * 5 / 8) as u8])).collect();
+        let palette = crate::generate_palette(16);
+        // Perceived difference between a pixel and the color it was matched to
+        let worst_error = |metric| {
+            let matcher = ColorMatcher::new(palette.clone(), metric);
+            gradient.iter().map(|pixel| {
+                let (source, matched) = (rgb_to_oklab(pixel), rgb_to_oklab(&palette[matcher.nearest_index(pixel)]));
+                (0..3).map(|c| (source[c] - matched[c]).powi(2)).sum::<f32>().sqrt()
+            }).fold(0.0, f32::max)
+        };
+        assert!(worst_error(DistanceMetric::Oklab) < worst_error(DistanceMetric::Rgb));
+    }
+}
@@ -0,0 +1,205 @@
+use image::{Luma, Rgb};
+use std::collections::HashMap;
+use crate::color::{linear_to_srgb, srgb_to_linear, ColorMatcher, DistanceMetric};
+use crate::{Quantized, MAX_PALETTE_COLORS};
+use pyo3::prelude::*;
+
+// Darken every `spacing`-th row by `intensity` (0.0 leaves it, 1.0 makes it black) for a CRT look.
+// With `linear` the darkening scales linear light, which looks more even than scaling sRGB values.
+pub fn scanlines(quantized: &mut Quantized, spacing: u32, intensity: f32, linear: bool) -> PyResult<()> {
+    let keep = 1.0 - intensity;
+    let darken = |c: u8| {
+        if linear {
+            linear_to_srgb(srgb_to_linear(c) * keep)
+        } else {
+            (c as f32 * keep).round().clamp(0.0, 255.0) as u8
+        }
+    };
+    
+    for (_, y, pixel) in quantized.image.enumerate_pixels_mut() {
+        if y % spacing == spacing - 1 {
+            *pixel = Rgb(pixel.0.map(darken));
+        }
+    }
+    reindex(quantized)
+}
+
+// Range `gamma` is clamped to, beyond it everything but black and white is pushed to one of the two
+pub const MIN_GAMMA: f32 = 0.1;
+pub const MAX_GAMMA: f32 = 10.0;
+
+// Apply an output gamma: every channel becomes 255 * (c / 255)^(1 / gamma), so values above 1.0
+// brighten the midtones and values below darken them while black and white stay put. Palette and
+// pixels go through the same table, every pixel keeps its index.
+pub fn output_gamma(quantized: &mut Quantized, gamma: f32) {
+    let table = gamma_table(gamma);
+    for color in quantized.palette.iter_mut().chain(quantized.image.pixels_mut()) {
+        *color = Rgb(color.0.map(|c| table[c as usize]));
+    }
+}
+
+// The `output_gamma` curve for every channel value
+pub fn gamma_table(gamma: f32) -> [u8; 256] {
+    std::array::from_fn(|c| (255.0 * (c as f32 / 255.0).powf(1.0 / gamma)).round() as u8)
+}
+
+// Distance from the center (as a fraction of the way to the corners) where the vignette starts, and
+// how much of the brightness it takes away at the corners at full strength
+const VIGNETTE_START: f32 = 0.35;
+const VIGNETTE_DEPTH: f32 = 0.65;
+
+// Darken the image towards the corners by `strength` (0.0-1.0) with a smooth radial falloff, then
+// match every pixel against the palette again so the result keeps to its colors. With `linear` the
+// falloff scales linear light, like `scanlines`.
+pub fn vignette(quantized: &mut Quantized, strength: f32, linear: bool, metric: DistanceMetric) {
+    let (width, height) = quantized.image.dimensions();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let corner = (cx * cx + cy * cy).sqrt().max(f32::EPSILON);
+    let matcher = ColorMatcher::new(quantized.palette.clone(), metric);
+    
+    for ((x, y, pixel), index) in quantized.image.enumerate_pixels_mut().zip(quantized.indices.pixels_mut()) {
+        let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+        let t = (((dx * dx + dy * dy).sqrt() / corner - VIGNETTE_START) / (1.0 - VIGNETTE_START)).clamp(0.0, 1.0);
+        // Smoothstep, so the darkening eases in without a visible edge
+        let keep = 1.0 - strength * VIGNETTE_DEPTH * t * t * (3.0 - 2.0 * t);
+        if keep >= 1.0 {
+            continue;
+        }
+        let darkened = Rgb(pixel.0.map(|c| {
+            if linear {
+                linear_to_srgb(srgb_to_linear(c) * keep)
+            } else {
+                (c as f32 * keep).round() as u8
+            }
+        }));
+        let nearest = matcher.nearest_index(&darkened);
+        *pixel = matcher.palette()[nearest];
+        *index = Luma([nearest as u16]);
+    }
+}
+
+// Width and height of the character cells that share a color pair under ZX Spectrum attribute clash
+pub const ATTRIBUTE_CELL: (u32, u32) = (8, 8);
+
+// Columns and rows of block graphics cells ("sixels") in a Teletext mosaic character
+pub const TELETEXT_SIXELS: (u32, u32) = (2, 3);
+
+// ZX Spectrum style attribute clash: each `cell` (width, height) keeps only its two most used palette
+// colors (ink and paper), and every other pixel in it is matched to whichever of the two is nearer.
+pub fn attribute_clash(quantized: &mut Quantized, metric: DistanceMetric, cell: (u32, u32)) {
+    limit_cell_colors(quantized, metric, cell, 2, None);
+}
+
+// Width and height of a C64 multicolor cell in output pixels, 4 double-width pixels across
+pub const MULTICOLOR_CELL: (u32, u32) = (8, 8);
+
+// Commodore 64 multicolor mode: one background color shared by the whole screen, the most used one,
+// and three more of its own in every cell
+pub fn c64_multicolor(quantized: &mut Quantized, metric: DistanceMetric) {
+    let mut counts = vec![0usize; quantized.palette.len()];
+    for index in quantized.indices.pixels() {
+        counts[index[0] as usize] += 1;
+    }
+    let background = (0..counts.len()).max_by_key(|&index| (counts[index], std::cmp::Reverse(index))).unwrap_or(0);
+    limit_cell_colors(quantized, metric, MULTICOLOR_CELL, 4, Some(background as u16));
+}
+
+// Keep `colors` palette colors in each `cell`, `shared` plus its most used others, and match every
+// other pixel in it to the nearest of them. Ties go to the lower palette index so the result is deterministic.
+fn limit_cell_colors(quantized: &mut Quantized, metric: DistanceMetric, cell: (u32, u32), colors: usize, shared: Option<u16>) {
+    let (width, height) = quantized.indices.dimensions();
+    let (cell_width, cell_height) = cell;
+    for top in (0..height).step_by(cell_height as usize) {
+        for left in (0..width).step_by(cell_width as usize) {
+            let cell: Vec<(u32, u32)> = (top..(top + cell_height).min(height))
+                .flat_map(|y| (left..(left + cell_width).min(width)).map(move |x| (x, y)))
+                .collect();
+            
+            let mut counts: HashMap<u16, usize> = HashMap::new();
+            for &(x, y) in &cell {
+                *counts.entry(quantized.indices.get_pixel(x, y)[0]).or_insert(0) += 1;
+            }
+            let mut ranked: Vec<(u16, usize)> = counts.into_iter().filter(|&(index, _)| Some(index) != shared).collect();
+            ranked.sort_by_key(|&(index, count)| (std::cmp::Reverse(count), index));
+            let own = colors - shared.iter().count();
+            if ranked.len() <= own {
+                continue;
+            }
+            let kept: Vec<u16> = shared.into_iter().chain(ranked[..own].iter().map(|&(index, _)| index)).collect();
+            let matcher = ColorMatcher::new(kept.iter().map(|&index| quantized.palette[index as usize]).collect(), metric);
+            
+            for &(x, y) in &cell {
+                let index = quantized.indices.get_pixel(x, y)[0];
+                if kept.contains(&index) {
+                    continue;
+                }
+                let chosen = kept[matcher.nearest_index(quantized.image.get_pixel(x, y))];
+                quantized.indices.put_pixel(x, y, Luma([chosen]));
+                quantized.image.put_pixel(x, y, quantized.palette[chosen as usize]);
+            }
+        }
+    }
+}
+
+// How steep the luminance gradient (in levels per pixel) has to be for `outline` to count it as an edge
+const OUTLINE_THRESHOLD: f32 = 48.0;
+
+// Draw `color` along the strong edges of the quantized image, for a cartoon style border. Edges
+// are found with a Sobel filter on luminance, so flat areas and soft dithering stay untouched, and
+// only the darker side of each edge is kept so a `thickness` of 1 gives a one pixel line. Thicker
+// outlines grow that line by `thickness - 1` pixels in every direction. The color is added to the
+// palette if it isn't in it already.
+pub fn outline(quantized: &mut Quantized, color: Rgb<u8>, thickness: u32) -> PyResult<()> {
+    let (width, height) = quantized.image.dimensions();
+    if width == 0 || height == 0 {
+        return Ok(());
+    }
+    let luma: Vec<f32> = quantized.image.pixels().map(crate::luminance).collect();
+    let at = |x: i64, y: i64| luma[(y.clamp(0, height as i64 - 1) * width as i64 + x.clamp(0, width as i64 - 1)) as usize];
+    
+    let mut edges = vec![false; luma.len()];
+    for y in 0..height as i64 {
+        for x in 0..width as i64 {
+            let gx = (at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)) - (at(x - 1, y - 1) + 2.0 * at(x - 1, y) + at(x - 1, y + 1));
+            let gy = (at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)) - (at(x - 1, y - 1) + 2.0 * at(x, y - 1) + at(x + 1, y - 1));
+            // The kernels weigh four pixels on each side, so a quarter of them is the step per pixel
+            let strong = (gx * gx + gy * gy).sqrt() / 4.0 > OUTLINE_THRESHOLD;
+            let mean = (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy))).map(|(dx, dy)| at(x + dx, y + dy)).sum::<f32>() / 9.0;
+            edges[(y * width as i64 + x) as usize] = strong && at(x, y) <= mean;
+        }
+    }
+    
+    let reach = thickness.saturating_sub(1) as i64;
+    for (x, y, pixel) in quantized.image.enumerate_pixels_mut() {
+        let (x, y) = (x as i64, y as i64);
+        let near_edge = (y - reach..=y + reach)
+            .filter(|&ny| ny >= 0 && ny < height as i64)
+            .any(|ny| (x - reach..=x + reach).filter(|&nx| nx >= 0 && nx < width as i64).any(|nx| edges[(ny * width as i64 + nx) as usize]));
+        if near_edge {
+            *pixel = color;
+        }
+    }
+    reindex(quantized)
+}
+
+// Effects that recolor pixels leave the image with colors outside the palette. Append those colors
+// to the palette and point the indices at them again, so indexed output and `return_palette` stay exact.
+fn reindex(quantized: &mut Quantized) -> PyResult<()> {
+    let mut lookup: HashMap<Rgb<u8>, u16> = HashMap::with_capacity(quantized.palette.len());
+    for (index, &color) in quantized.palette.iter().enumerate().rev() {
+        lookup.insert(color, index as u16);
+    }
+    
+    for (pixel, index) in quantized.image.pixels().zip(quantized.indices.pixels_mut()) {
+        let next = quantized.palette.len();
+        let position = *lookup.entry(*pixel).or_insert_with(|| {
+            quantized.palette.push(*pixel);
+            next as u16
+        });
+        if quantized.palette.len() > MAX_PALETTE_COLORS {
+            return Err(PyErr::new::<crate::errors::Pixel8BitError, _>(format!("Effects produced more than {} distinct colors", MAX_PALETTE_COLORS)));
+        }
+        *index = Luma([position]);
+    }
+    Ok(())
+}
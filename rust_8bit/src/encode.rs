@@ -0,0 +1,325 @@
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::{ImageBuffer, Rgb, Rgba};
+use pyo3::prelude::*;
+use std::borrow::Cow;
+use std::io::Cursor;
+use crate::{Options, Quantized};
+
+// File formats the quantized image can be written as
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Png,
+    PngIndexed,
+    Gif,
+    Jpeg,
+    WebP,
+    Bmp,
+    Qoi,
+    // Needs a build with the "avif" feature, see `encode_avif`
+    Avif,
+}
+
+// Names accepted by `output_format`, in the order they are listed in error messages
+pub const OUTPUT_FORMATS: &[(&str, OutputFormat)] = &[
+    ("png", OutputFormat::Png),
+    ("png_indexed", OutputFormat::PngIndexed),
+    ("gif", OutputFormat::Gif),
+    ("jpeg", OutputFormat::Jpeg),
+    ("webp", OutputFormat::WebP),
+    ("bmp", OutputFormat::Bmp),
+    ("qoi", OutputFormat::Qoi),
+    ("avif", OutputFormat::Avif),
+];
+
+// Error for output formats whose encoder was left out of the build
+fn missing_feature(format: &str) -> PyErr {
+    PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("output_format '{}' requires rust_8bit to be built with the '{}' feature", format, format))
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        let format = OUTPUT_FORMATS.iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|&(_, format)| format)
+            .ok_or_else(|| {
+                let valid: Vec<&str> = OUTPUT_FORMATS.iter().map(|(candidate, _)| *candidate).collect();
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown output format '{}', expected one of: {}", name, valid.join(", ")))
+            })?;
+        if format == OutputFormat::Avif && !cfg!(feature = "avif") {
+            return Err(missing_feature(name));
+        }
+        Ok(format)
+    }
+    
+    // Formats that can store a full alpha channel alongside the RGB image
+    pub fn supports_alpha(self) -> bool {
+        matches!(self, OutputFormat::Png | OutputFormat::WebP | OutputFormat::Bmp | OutputFormat::Qoi | OutputFormat::Avif)
+    }
+}
+
+// How hard the PNG encoders try to shrink their output
+#[derive(Clone, Copy, PartialEq)]
+pub enum PngCompression {
+    Fast,
+    Default,
+    Best,
+}
+
+// Names accepted by `png_compression`, in the order they are listed in error messages
+pub const PNG_COMPRESSIONS: &[(&str, PngCompression)] = &[
+    ("fast", PngCompression::Fast),
+    ("default", PngCompression::Default),
+    ("best", PngCompression::Best),
+];
+
+impl PngCompression {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        PNG_COMPRESSIONS.iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|&(_, compression)| compression)
+            .ok_or_else(|| {
+                let valid: Vec<&str> = PNG_COMPRESSIONS.iter().map(|(candidate, _)| *candidate).collect();
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown PNG compression '{}', expected one of: {}", name, valid.join(", ")))
+            })
+    }
+    
+    // The level as the `png` crate's encoder takes it
+    pub fn level(self) -> png::Compression {
+        match self {
+            PngCompression::Fast => png::Compression::Fast,
+            PngCompression::Default => png::Compression::Default,
+            PngCompression::Best => png::Compression::Best,
+        }
+    }
+    
+    fn compression_type(self) -> CompressionType {
+        match self {
+            PngCompression::Fast => CompressionType::Fast,
+            PngCompression::Default => CompressionType::Default,
+            PngCompression::Best => CompressionType::Best,
+        }
+    }
+}
+
+// Encode a quantized image in the format selected by `options`
+pub fn encode(quantized: &Quantized, options: &Options) -> PyResult<Vec<u8>> {
+    match options.output_format {
+        OutputFormat::Png => encode_png(quantized, options.png_compression),
+        OutputFormat::PngIndexed => encode_indexed_png(quantized, options.transparent_color, options.pad_palette_to, options.png_compression),
+        OutputFormat::Gif => encode_gif(quantized, options.transparent_color, options.pad_palette_to),
+        OutputFormat::Jpeg => encode_with_image(quantized, image::ImageOutputFormat::Jpeg(options.quality)),
+        // The `image` WebP encoder is lossless, which suits hard-edged pixel art anyway
+        OutputFormat::WebP => encode_with_image(quantized, image::ImageOutputFormat::WebP),
+        OutputFormat::Bmp => encode_with_image(quantized, image::ImageOutputFormat::Bmp),
+        OutputFormat::Qoi => encode_qoi(quantized),
+        OutputFormat::Avif => encode_avif(quantized, options.quality),
+    }
+}
+
+// Write the image through one of the `image` crate's encoders, as RGBA when alpha was kept
+fn encode_with_image(quantized: &Quantized, format: image::ImageOutputFormat) -> PyResult<Vec<u8>> {
+    let mut output_bytes = Cursor::new(Vec::new());
+    let result = match &quantized.alpha {
+        Some(_) => with_alpha(quantized).write_to(&mut output_bytes, format),
+        None => quantized.image.write_to(&mut output_bytes, format),
+    };
+    result.map_err(|e| PyErr::new::<crate::errors::EncodeError, _>(format!("Failed to encode image: {}", e)))?;
+    Ok(output_bytes.into_inner())
+}
+
+// The quantized colors with the kept alpha channel added back
+fn with_alpha(quantized: &Quantized) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(quantized.image.width(), quantized.image.height(), |x, y| {
+        let [r, g, b] = quantized.image.get_pixel(x, y).0;
+        let a = quantized.alpha.as_ref().map_or(255, |alpha| alpha.get_pixel(x, y)[0]);
+        Rgba([r, g, b, a])
+    })
+}
+
+// Write an RGB (or RGBA when alpha was kept) PNG at the given compression level, with the `image`
+// crate's default filtering
+fn encode_png(quantized: &Quantized, compression: PngCompression) -> PyResult<Vec<u8>> {
+    let mut output_bytes = Vec::new();
+    let encoder = PngEncoder::new_with_quality(&mut output_bytes, compression.compression_type(), FilterType::default());
+    let result = match &quantized.alpha {
+        Some(_) => with_alpha(quantized).write_with_encoder(encoder),
+        None => quantized.image.write_with_encoder(encoder),
+    };
+    result.map_err(|e| PyErr::new::<crate::errors::EncodeError, _>(format!("Failed to encode image: {}", e)))?;
+    Ok(output_bytes)
+}
+
+// Write a QOI image, RGBA when alpha was kept. Runs of one color and repeats of recently seen
+// colors take a byte or two each, which is most of what a quantized image is made of.
+fn encode_qoi(quantized: &Quantized) -> PyResult<Vec<u8>> {
+    let (width, height) = quantized.image.dimensions();
+    let result = match &quantized.alpha {
+        Some(_) => qoi::encode_to_vec(with_alpha(quantized).as_raw(), width, height),
+        None => qoi::encode_to_vec(quantized.image.as_raw(), width, height),
+    };
+    result.map_err(|e| PyErr::new::<crate::errors::EncodeError, _>(format!("Failed to encode image: {}", e)))
+}
+
+// Write a lossy AVIF image at JPEG-style `quality` (0-100), RGBA when alpha was kept
+#[cfg(feature = "avif")]
+fn encode_avif(quantized: &Quantized, quality: u8) -> PyResult<Vec<u8>> {
+    use rgb::FromSlice;
+    let (width, height) = (quantized.image.width() as usize, quantized.image.height() as usize);
+    let encoder = ravif::Encoder::new().with_quality(quality as f32).with_alpha_quality(quality as f32);
+    let result = match &quantized.alpha {
+        Some(_) => encoder.encode_rgba(ravif::Img::new(with_alpha(quantized).as_raw().as_rgba(), width, height)),
+        None => encoder.encode_rgb(ravif::Img::new(quantized.image.as_raw().as_rgb(), width, height)),
+    };
+    result
+        .map(|encoded| encoded.avif_file)
+        .map_err(|e| PyErr::new::<crate::errors::EncodeError, _>(format!("Failed to encode image: {}", e)))
+}
+
+// `OutputFormat::from_name` already refuses "avif" without the feature
+#[cfg(not(feature = "avif"))]
+fn encode_avif(_quantized: &Quantized, _quality: u8) -> PyResult<Vec<u8>> {
+    Err(missing_feature("avif"))
+}
+
+// Palette positions narrowed to bytes, for formats limited to a 256-entry color table.
+// Also returns the index that should be marked transparent, see `byte_table`.
+fn byte_indices(quantized: &Quantized, transparent: Option<Rgb<u8>>, format_name: &str) -> PyResult<(Vec<u8>, Option<u8>)> {
+    let (table, transparent_index) = byte_table(&quantized.palette, transparent, format_name)?;
+    let indices = quantized.indices.as_raw().iter().map(|&index| table[index as usize]).collect();
+    Ok((indices, transparent_index))
+}
+
+// The byte each palette position is written as, plus the index that should be marked transparent if
+// `transparent` is in the palette. Duplicate entries of that color are folded onto it so every
+// matching pixel disappears.
+pub fn byte_table(palette: &[Rgb<u8>], transparent: Option<Rgb<u8>>, format_name: &str) -> PyResult<(Vec<u8>, Option<u8>)> {
+    if palette.len() > 256 {
+        return Err(PyErr::new::<crate::errors::EncodeError, _>(format!("{} output supports at most 256 colors, the palette has {}", format_name, palette.len())));
+    }
+    let transparent_index = transparent.and_then(|color| palette.iter().position(|&c| c == color));
+    let table = palette.iter().enumerate()
+        .map(|(index, &color)| match transparent_index {
+            Some(t) if color == palette[t] => t as u8,
+            _ => index as u8,
+        })
+        .collect();
+    Ok((table, transparent_index.map(|t| t as u8)))
+}
+
+// tRNS entries marking `index` transparent. The chunk only needs to run up to the last entry that isn't opaque
+pub fn transparency_chunk(index: u8) -> Vec<u8> {
+    let mut trns = vec![255u8; index as usize + 1];
+    trns[index as usize] = 0;
+    trns
+}
+
+// Flatten the palette into r, g, b triplets
+pub fn color_table(palette: &[Rgb<u8>]) -> Vec<u8> {
+    palette.iter().flat_map(|color| color.0).collect()
+}
+
+// `color_table` filled up with black entries to `pad_to` colors, so files made with different
+// palettes get color tables of the same size. No pixel refers to the filler.
+pub fn padded_color_table(palette: &[Rgb<u8>], pad_to: Option<usize>) -> PyResult<Vec<u8>> {
+    let mut table = color_table(palette);
+    if let Some(size) = pad_to {
+        if palette.len() > size {
+            return Err(PyErr::new::<crate::errors::EncodeError, _>(format!("The palette has {} colors, more than pad_palette_to={}", palette.len(), size)));
+        }
+        table.resize(size * 3, 0);
+    }
+    Ok(table)
+}
+
+// Write an 8-bit palette PNG with the colors in the PLTE chunk
+fn encode_indexed_png(quantized: &Quantized, transparent: Option<Rgb<u8>>, pad_to: Option<usize>, compression: PngCompression) -> PyResult<Vec<u8>> {
+    let (indices, transparent_index) = byte_indices(quantized, transparent, "Indexed PNG")?;
+    let (width, height) = quantized.indices.dimensions();
+    
+    let to_err = |e: png::EncodingError| PyErr::new::<crate::errors::EncodeError, _>(format!("Failed to encode image: {}", e));
+    let mut output_bytes = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut output_bytes, width, height);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_compression(compression.level());
+        encoder.set_palette(padded_color_table(&quantized.palette, pad_to)?);
+        if let Some(t) = transparent_index {
+            encoder.set_trns(transparency_chunk(t));
+        }
+        let mut writer = encoder.write_header().map_err(to_err)?;
+        writer.write_image_data(&indices).map_err(to_err)?;
+    }
+    Ok(output_bytes)
+}
+
+// Indices of the whole image as a GIF frame, not yet given a position in an animation
+fn gif_frame(quantized: &Quantized, transparent: Option<Rgb<u8>>) -> PyResult<gif::Frame<'static>> {
+    let (indices, transparent_index) = byte_indices(quantized, transparent, "GIF")?;
+    let (width, height) = quantized.indices.dimensions();
+    let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(w), Ok(h)) => (w, h),
+        _ => return Err(PyErr::new::<crate::errors::EncodeError, _>(format!("GIF output is limited to 65535x65535 pixels, got {}x{}", width, height))),
+    };
+    
+    Ok(gif::Frame {
+        width,
+        height,
+        buffer: Cow::Owned(indices),
+        transparent: transparent_index,
+        ..gif::Frame::default()
+    })
+}
+
+fn gif_error(e: gif::EncodingError) -> PyErr {
+    PyErr::new::<crate::errors::EncodeError, _>(format!("Failed to encode image: {}", e))
+}
+
+// Write a single-frame GIF with the palette as the global color table
+fn encode_gif(quantized: &Quantized, transparent: Option<Rgb<u8>>, pad_to: Option<usize>) -> PyResult<Vec<u8>> {
+    let frame = gif_frame(quantized, transparent)?;
+    
+    let mut output_bytes = Vec::new();
+    {
+        let mut encoder = gif::Encoder::new(&mut output_bytes, frame.width, frame.height, &padded_color_table(&quantized.palette, pad_to)?).map_err(gif_error)?;
+        encoder.write_frame(&frame).map_err(gif_error)?;
+    }
+    Ok(output_bytes)
+}
+
+// Palette cycling settings: the number of frames, the inclusive range of palette indices
+// that rotates by one step per frame, and the delay between frames in hundredths of a second
+pub struct Cycle {
+    pub frames: usize,
+    pub start: usize,
+    pub end: usize,
+    pub delay: u16,
+}
+
+// Write a looping GIF that repeats the same pixels with the colors in `cycle`'s range rotated
+// a step further every frame. Only the color table changes, so motion comes purely from the palette.
+// The global table and every frame's are padded to `pad_to` colors like `encode_gif`'s.
+pub fn encode_cycling_gif(quantized: &Quantized, transparent: Option<Rgb<u8>>, pad_to: Option<usize>, cycle: &Cycle) -> PyResult<Vec<u8>> {
+    if cycle.frames == 0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("frames must be at least 1"));
+    }
+    if cycle.start > cycle.end || cycle.end >= quantized.palette.len() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Cycle range {}-{} must lie within the palette's {} colors", cycle.start, cycle.end, quantized.palette.len())));
+    }
+    let mut frame = gif_frame(quantized, transparent)?;
+    frame.delay = cycle.delay;
+    
+    let mut output_bytes = Vec::new();
+    {
+        let mut encoder = gif::Encoder::new(&mut output_bytes, frame.width, frame.height, &padded_color_table(&quantized.palette, pad_to)?).map_err(gif_error)?;
+        encoder.set_repeat(gif::Repeat::Infinite).map_err(gif_error)?;
+        
+        let mut palette = quantized.palette.clone();
+        for _ in 0..cycle.frames {
+            frame.palette = Some(padded_color_table(&palette, pad_to)?);
+            encoder.write_frame(&frame).map_err(gif_error)?;
+            palette[cycle.start..=cycle.end].rotate_right(1);
+        }
+    }
+    Ok(output_bytes)
+}
@@ -0,0 +1,23 @@
+// pyo3 0.19's exception macro checks a cfg of its own that newer compilers don't know about
+#![allow(unexpected_cfgs)]
+
+use pyo3::create_exception;
+use pyo3::prelude::*;
+
+// Exceptions raised when a conversion fails part way, as opposed to a plain ValueError for invalid
+// arguments. The base derives from ValueError so code catching that keeps working.
+create_exception!(rust_8bit, Pixel8BitError, pyo3::exceptions::PyValueError, "Base class for conversion failures raised by rust_8bit.");
+create_exception!(rust_8bit, DecodeError, Pixel8BitError, "The input image could not be read.");
+create_exception!(rust_8bit, EncodeError, Pixel8BitError, "The result could not be written in the requested output format.");
+create_exception!(rust_8bit, InvalidPaletteError, Pixel8BitError, "The requested palette is unknown, empty or too large.");
+create_exception!(rust_8bit, ImageTooLargeError, Pixel8BitError, "The input image is larger than max_dimension or max_pixels allow.");
+
+// Make the exception classes importable from the module
+pub fn register(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add("Pixel8BitError", py.get_type::<Pixel8BitError>())?;
+    m.add("DecodeError", py.get_type::<DecodeError>())?;
+    m.add("EncodeError", py.get_type::<EncodeError>())?;
+    m.add("InvalidPaletteError", py.get_type::<InvalidPaletteError>())?;
+    m.add("ImageTooLargeError", py.get_type::<ImageTooLargeError>())?;
+    Ok(())
+}
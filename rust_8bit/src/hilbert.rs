@@ -0,0 +1,64 @@
+// Hilbert curve generalized to any rectangle
+
+// Every pixel of a `width` x `height` grid, in the order a Hilbert-like space-filling curve visits
+// them. Consecutive pixels are always neighbours, also for sizes that aren't a power of two.
+//
+// This is the "generalized Hilbert" construction: the rectangle is split in two along its long side,
+// or in three for squarish ones, and each part is walked recursively with the curve reoriented so
+// the parts join up.
+pub fn curve(width: u32, height: u32) -> Vec<(u32, u32)> {
+    let mut points = Vec::with_capacity(width as usize * height as usize);
+    if width == 0 || height == 0 {
+        return points;
+    }
+    let (w, h) = (width as i64, height as i64);
+    if w >= h {
+        walk(&mut points, 0, 0, w, 0, 0, h);
+    } else {
+        walk(&mut points, 0, 0, 0, h, w, 0);
+    }
+    points
+}
+
+// Walk the rectangle starting at (x, y) with major axis (ax, ay) and minor axis (bx, by)
+#[allow(clippy::too_many_arguments)]
+fn walk(points: &mut Vec<(u32, u32)>, x: i64, y: i64, ax: i64, ay: i64, bx: i64, by: i64) {
+    let w = (ax + ay).abs();
+    let h = (bx + by).abs();
+    let (dax, day) = (ax.signum(), ay.signum());
+    let (dbx, dby) = (bx.signum(), by.signum());
+    
+    // A single row or column is walked straight
+    if h == 1 || w == 1 {
+        let (steps, dx, dy) = if h == 1 { (w, dax, day) } else { (h, dbx, dby) };
+        for i in 0..steps {
+            points.push(((x + dx * i) as u32, (y + dy * i) as u32));
+        }
+        return;
+    }
+    
+    // Halves round towards negative infinity so mirrored axes split the same way
+    let (mut ax2, mut ay2) = (ax.div_euclid(2), ay.div_euclid(2));
+    let (mut bx2, mut by2) = (bx.div_euclid(2), by.div_euclid(2));
+    let w2 = (ax2 + ay2).abs();
+    let h2 = (bx2 + by2).abs();
+    
+    if 2 * w > 3 * h {
+        // Long rectangle: two halves along the major axis, the first kept an even length if possible
+        if w2 % 2 == 1 && w > 2 {
+            ax2 += dax;
+            ay2 += day;
+        }
+        walk(points, x, y, ax2, ay2, bx, by);
+        walk(points, x + ax2, y + ay2, ax - ax2, ay - ay2, bx, by);
+    } else {
+        // Up along the minor axis, across, then back down
+        if h2 % 2 == 1 && h > 2 {
+            bx2 += dbx;
+            by2 += dby;
+        }
+        walk(points, x, y, bx2, by2, ax2, ay2);
+        walk(points, x + bx2, y + by2, ax, ay, bx - bx2, by - by2);
+        walk(points, x + (ax - dax) + (bx2 - dbx), y + (ay - day) + (by2 - dby), -bx2, -by2, -(ax - ax2), -(ay - ay2));
+    }
+}
@@ -0,0 +1,127 @@
+// k-d tree over palette colors for exact nearest-color search in O(log n) per pixel
+
+struct Node {
+    point: [f32; 3],
+    // Position of this color in the palette
+    index: usize,
+    axis: usize,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+pub struct KdTree {
+    nodes: Vec<Node>,
+    root: Option<usize>,
+}
+
+impl KdTree {
+    // Build a balanced tree by splitting at the median of each axis in turn
+    pub fn new(points: &[[f32; 3]]) -> Self {
+        let mut tree = KdTree { nodes: Vec::with_capacity(points.len()), root: None };
+        let mut indices: Vec<usize> = (0..points.len()).collect();
+        tree.root = tree.build(points, &mut indices, 0);
+        tree
+    }
+    
+    fn build(&mut self, points: &[[f32; 3]], indices: &mut [usize], depth: usize) -> Option<usize> {
+        if indices.is_empty() {
+            return None;
+        }
+        
+        let axis = depth % 3;
+        indices.sort_by(|&a, &b| points[a][axis].total_cmp(&points[b][axis]).then(a.cmp(&b)));
+        let median = indices.len() / 2;
+        let index = indices[median];
+        
+        let (lower, upper) = indices.split_at_mut(median);
+        let left = self.build(points, lower, depth + 1);
+        let right = self.build(points, &mut upper[1..], depth + 1);
+        
+        self.nodes.push(Node { point: points[index], index, axis, left, right });
+        Some(self.nodes.len() - 1)
+    }
+    
+    // Palette index of the point closest to `target`. Ties go to the lowest palette index,
+    // matching a linear scan that keeps the first minimum.
+    pub fn nearest(&self, target: &[f32; 3]) -> Option<usize> {
+        let mut best = None;
+        let mut best_distance = f32::MAX;
+        if let Some(root) = self.root {
+            self.search(root, target, &mut best, &mut best_distance);
+        }
+        best
+    }
+    
+    fn search(&self, node: usize, target: &[f32; 3], best: &mut Option<usize>, best_distance: &mut f32) {
+        let current = &self.nodes[node];
+        
+        let d0 = target[0] - current.point[0];
+        let d1 = target[1] - current.point[1];
+        let d2 = target[2] - current.point[2];
+        let distance = d0 * d0 + d1 * d1 + d2 * d2;
+        let closer = match *best {
+            Some(index) => distance < *best_distance || (distance == *best_distance && current.index < index),
+            None => true,
+        };
+        if closer {
+            *best = Some(current.index);
+            *best_distance = distance;
+        }
+        
+        // Visit the side containing the target first, and the other side only if the
+        // splitting plane is close enough to hold something at least as near
+        let offset = target[current.axis] - current.point[current.axis];
+        let (near, far) = if offset < 0.0 { (current.left, current.right) } else { (current.right, current.left) };
+        if let Some(near) = near {
+            self.search(near, target, best, best_distance);
+        }
+        if let Some(far) = far {
+            if offset * offset <= *best_distance {
+                self.search(far, target, best, best_distance);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    struct XorShift(u64);
+    
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+        
+        // Channel value on a coarse grid of steps of 16, so equally distant points are common
+        fn coarse(&mut self) -> [f32; 3] {
+            [0; 3].map(|_: u8| (self.next() % 17 * 16) as f32)
+        }
+    }
+    
+    #[test]
+    fn matches_a_linear_scan() {
+        let mut rng = XorShift(0x1234_5678_9ABC_DEF1);
+        // Repeats included, those have to resolve to the lowest palette index too
+        let mut points: Vec<[f32; 3]> = (0..160).map(|_| rng.coarse()).collect();
+        points.extend_from_within(..32);
+        let tree = KdTree::new(&points);
+        
+        let mut ties = 0;
+        for _ in 0..4000 {
+            let target = [0; 3].map(|_: u8| (rng.next() % 256) as f32);
+            let distance = |i: &usize| points[*i].iter().zip(&target).map(|(p, t)| ((p - t) * (p - t)) as u32).sum::<u32>();
+            let expected = (0..points.len()).min_by_key(distance);
+            assert_eq!(tree.nearest(&target), expected);
+            let best = expected.map(|i| distance(&i));
+            ties += ((0..points.len()).filter(|i| Some(distance(i)) == best).count() > 1) as usize;
+        }
+        assert!(tree.nearest(&[0.0; 3]).is_some() && KdTree::new(&[]).nearest(&[0.0; 3]).is_none());
+        // The coarse grid has to have produced ties for the test to cover them
+        assert!(ties > 0);
+    }
+}
@@ -1,52 +1,1673 @@
 use pyo3::prelude::*;
-use pyo3::types::PyBytes;
-use image::{GenericImageView, ImageBuffer, Rgb};
-use std::io::Cursor;
+use pyo3::types::{PyBytes, PyDict};
+use image::{DynamicImage, ImageBuffer, Luma, Rgb, Rgba};
+use numpy::ndarray::{Array2, Array3};
+use numpy::{IntoPyArray, PyArrayDyn};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use color::{BitLayout, ColorMatcher, DistanceMetric};
+use encode::{OutputFormat, PngCompression};
+use pixelate::{TileShape, Tiling};
 
+mod adaptive;
+mod animation;
+mod ascii;
+mod adjust;
+mod bluenoise;
+mod cmyk;
+mod color;
+mod effects;
+mod encode;
+mod errors;
+mod hilbert;
+mod kdtree;
+mod orient;
+mod palettes;
+mod pixelate;
+mod progress;
+#[cfg(feature = "simd")]
+mod simd;
+mod stream;
+mod trace;
+
+// Error diffusion algorithms that can be selected through `dither_algorithm`
+#[derive(Clone, Copy, PartialEq)]
+enum DitherAlgorithm {
+    None,
+    FloydSteinberg,
+    Atkinson,
+    Ordered,
+    Jarvis,
+    Stucki,
+    Burkes,
+    Sierra,
+    SierraLite,
+    Riemersma,
+    BlueNoise,
+    Halftone,
+    // Error diffusion with the kernel given as `dither_kernel`
+    Custom,
+}
+
+// Names accepted by `dither_algorithm`, in the order they are listed in error messages
+const DITHER_ALGORITHMS: &[(&str, DitherAlgorithm)] = &[
+    ("none", DitherAlgorithm::None),
+    ("floyd_steinberg", DitherAlgorithm::FloydSteinberg),
+    ("atkinson", DitherAlgorithm::Atkinson),
+    ("ordered", DitherAlgorithm::Ordered),
+    ("jarvis", DitherAlgorithm::Jarvis),
+    ("stucki", DitherAlgorithm::Stucki),
+    ("burkes", DitherAlgorithm::Burkes),
+    ("sierra", DitherAlgorithm::Sierra),
+    ("sierra_lite", DitherAlgorithm::SierraLite),
+    ("riemersma", DitherAlgorithm::Riemersma),
+    ("blue_noise", DitherAlgorithm::BlueNoise),
+    ("halftone", DitherAlgorithm::Halftone),
+    ("custom", DitherAlgorithm::Custom),
+];
+
+impl DitherAlgorithm {
+    fn from_name(name: &str) -> PyResult<Self> {
+        DITHER_ALGORITHMS.iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|&(_, algorithm)| algorithm)
+            .ok_or_else(|| {
+                let valid: Vec<&str> = DITHER_ALGORITHMS.iter().map(|(candidate, _)| *candidate).collect();
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown dither algorithm '{}', expected one of: {}", name, valid.join(", ")))
+            })
+    }
+}
+
+// Dot shapes for halftone dithering
+#[derive(Clone, Copy, PartialEq)]
+enum HalftoneShape {
+    Round,
+    Square,
+}
+
+// Names accepted by `halftone_shape`, in the order they are listed in error messages
+const HALFTONE_SHAPES: &[(&str, HalftoneShape)] = &[
+    ("round", HalftoneShape::Round),
+    ("square", HalftoneShape::Square),
+];
+
+impl HalftoneShape {
+    fn from_name(name: &str) -> PyResult<Self> {
+        HALFTONE_SHAPES.iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|&(_, shape)| shape)
+            .ok_or_else(|| {
+                let valid: Vec<&str> = HALFTONE_SHAPES.iter().map(|(candidate, _)| *candidate).collect();
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown halftone shape '{}', expected one of: {}", name, valid.join(", ")))
+            })
+    }
+}
+
+// How the palette is built when no preset or custom palette is given
+#[derive(Clone, Copy, PartialEq)]
+enum PaletteMode {
+    Fixed,
+    MedianCut,
+    KMeans,
+    Octree,
+    Popularity,
+    Grayscale,
+    Ehb,
+}
+
+// Names accepted by `palette_mode`, in the order they are listed in error messages
+const PALETTE_MODES: &[(&str, PaletteMode)] = &[
+    ("fixed", PaletteMode::Fixed),
+    ("median_cut", PaletteMode::MedianCut),
+    ("kmeans", PaletteMode::KMeans),
+    ("octree", PaletteMode::Octree),
+    ("popularity", PaletteMode::Popularity),
+    ("grayscale", PaletteMode::Grayscale),
+    ("ehb", PaletteMode::Ehb),
+];
+
+impl PaletteMode {
+    fn from_name(name: &str) -> PyResult<Self> {
+        PALETTE_MODES.iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|&(_, mode)| mode)
+            .ok_or_else(|| {
+                let valid: Vec<&str> = PALETTE_MODES.iter().map(|(candidate, _)| *candidate).collect();
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown palette mode '{}', expected one of: {}", name, valid.join(", ")))
+            })
+    }
+}
+
+// Settings for a conversion, built from the positional arguments plus any keyword arguments
+struct Options {
+    dither_algorithm: DitherAlgorithm,
+    // Threshold matrix size for ordered dithering (2, 4 or 8)
+    bayer_size: u32,
+    // Range in 0-255 channel units the ordered and blue-noise thresholds spread pixels over,
+    // estimated from the palette (see `ordered_spread`) unless given
+    dither_spread: Option<f32>,
+    // Width in pixels of the square cells halftone dots sit in, and the shape of the dots
+    halftone_spacing: u32,
+    halftone_shape: HalftoneShape,
+    // Kernel of the "custom" algorithm as (dx, dy, weight) entries, weights already divided by
+    // `dither_kernel_divisor` (the sum of the weights unless given)
+    dither_kernel: Option<Vec<(i32, i32, f32)>>,
+    // Alternate the scan direction on every row during error diffusion
+    serpentine: bool,
+    // Scales the propagated error, 0.0 (no diffusion) to 1.0 (full). Has no effect on ordered dithering
+    dither_strength: f32,
+    // Keep error diffusion from pushing a pixel more than `dither_clamp_margin` (0-255 channel units,
+    // linear light with `linear_rgb`) away from its source color, which stops errors piling up into
+    // lone dark or bright specks in smooth areas
+    dither_clamp: bool,
+    dither_clamp_margin: f32,
+    // Only diffuse error through pixels whose source luminance is below this (0-255), so highlights
+    // are matched cleanly and only the shadows get dithered
+    dither_shadows: Option<u8>,
+    // Keep error diffusion inside the cells of `attribute_clash`, `teletext_mosaic` or `c64_multicolor`,
+    // see `Options::dither_cell`. The first and last lay their cells out on the resized output, so
+    // they can't be isolated together with `target_width` or `target_height`.
+    dither_per_tile: bool,
+    // Amplitude in 0-255 channel units of the triangular noise added to every channel before matching,
+    // a cheap way to break up banding without error diffusion. 0.0 adds none.
+    predither_noise: f32,
+    // Fixed palette preset that replaces the generated palette and overrides `palette_size`
+    palette_preset: Option<&'static palettes::Preset>,
+    // Restricts the "cga" preset to the 4-color mode palette 0 or 1
+    cga_palette: Option<usize>,
+    // User supplied colors to quantize against instead of a generated palette
+    custom_palette: Option<Vec<Rgb<u8>>>,
+    // Fixed generated palette, or one adapted to the colors of the input image
+    palette_mode: PaletteMode,
+    // Make sure pure black and white are in the generated palette, see `with_extremes`. Needs a
+    // `palette_size` of at least 2.
+    preserve_extremes: bool,
+    // Round each channel to this many evenly spaced levels instead of matching against a palette
+    posterize_levels: Option<u8>,
+    // Round each channel to the levels a fixed hardware pixel format can store, e.g. 8 x 8 x 4 for RGB332
+    bit_layout: Option<BitLayout>,
+    // Two-tone mode: luminance above this becomes `foreground`, the rest `background`
+    threshold: Option<u8>,
+    // Colors for the threshold mode, white and black unless overridden
+    foreground: Option<Rgb<u8>>,
+    background: Option<Rgb<u8>>,
+    // Shadow and highlight colors: luminance is quantized to `palette_size` greys like "grayscale",
+    // which are then swapped for the colors the same fraction of the way along the ramp between them
+    duotone: Option<(Rgb<u8>, Rgb<u8>)>,
+    // Maximum refinement rounds and convergence threshold (in RGB units) for "kmeans"
+    kmeans_iterations: usize,
+    kmeans_tolerance: f32,
+    // Bits per channel "popularity" counts colors on (1-8), lower values pool similar shades
+    popularity_bits: u8,
+    // Seeds the pixel sampling and centroid initialization of "kmeans" and the `predither_noise`,
+    // a fixed default unless given
+    seed: u64,
+    // Color difference used to pick the nearest palette entry. "hsv" carries its (hue, saturation, value)
+    // weights and "ycbcr" how many times less a chroma difference counts than a luminance one
+    distance_metric: DistanceMetric,
+    // Added to every channel (in 0.0-1.0 units) before quantization, 0.0 leaves the image unchanged
+    brightness: f32,
+    // Scales every channel around mid grey before quantization, 1.0 leaves the image unchanged
+    contrast: f32,
+    // White balance from -1.0 (cooler, bluer) to 1.0 (warmer, redder) before quantization, 0.0 leaves the image unchanged
+    temperature: f32,
+    // Multiplies the HSV saturation before quantization, 0.0 is grayscale and values above 1.0 boost it
+    saturation: f32,
+    // Rotates the hue by this many degrees before quantization
+    hue_shift: f32,
+    // Sepia tone and invert (255 - value) the image before quantization, so the result still uses the palette
+    sepia: bool,
+    invert: bool,
+    // Unsharp mask amount on luminance (0.0-4.0) for the image that gets quantized, after pixelation,
+    // see `adjust::sharpen`. 0.0 leaves it unchanged
+    sharpen: f32,
+    // Average, diffuse error and measure "rgb" distances in linear light instead of on sRGB values
+    linear_rgb: bool,
+    // Exact-match color replacements applied to the finished output
+    recolor: Option<HashMap<Rgb<u8>, Rgb<u8>>>,
+    // Darken every `scanline_spacing`-th output row by `scanline_intensity` (0.0-1.0)
+    scanlines: bool,
+    scanline_spacing: u32,
+    scanline_intensity: f32,
+    // Output transfer curve applied to the final colors, see `effects::output_gamma`. 1.0 leaves them
+    // as they are, it has nothing to do with `linear_rgb`
+    gamma: f32,
+    // Darken the output towards the corners, from 0.0 (off) to 1.0, keeping to the palette
+    vignette_strength: f32,
+    // Limit every 8x8 cell of the output to two colors, like the ZX Spectrum's color attributes
+    attribute_clash: bool,
+    // Teletext block graphics: every character cell of 2x3 mosaic cells (`pixel_size` squares or
+    // `tile_width` x `tile_height` tiles, single pixels without either) gets two colors, like attribute clash
+    teletext_mosaic: bool,
+    // Commodore 64 multicolor cells: every 8x8 cell of the output gets the image's most used color as a
+    // shared background plus its own three most used colors. Combine with `wide_pixels` and the "c64"
+    // preset for the whole look
+    c64_multicolor: bool,
+    // Draw `outline_color` along strong edges of the output, `outline_thickness` pixels wide
+    outline: bool,
+    outline_color: Rgb<u8>,
+    outline_thickness: u32,
+    // Return `(image_bytes, palette)` instead of just the image bytes
+    return_palette: bool,
+    // Match pixels through a precomputed 32x32x32 lookup table instead of scanning the palette
+    lookup_cache: bool,
+    // Find nearest colors with a k-d tree over the palette, always done from `KD_TREE_MIN_COLORS` colors
+    kd_tree: bool,
+    // Size of the square blocks the image is averaged into before quantization, 1 keeps full resolution
+    pixel_size: u32,
+    // Mosaic cells the image is averaged into instead of `pixel_size` squares. Setting either size
+    // turns the mosaic on, the other defaults to the same value.
+    tile_width: Option<u32>,
+    tile_height: Option<u32>,
+    tile_shape: TileShape,
+    // Double the width of the `pixel_size` blocks or tiles (single pixels become 2x1 blocks), for the
+    // halved horizontal resolution of modes like C64 multicolor
+    wide_pixels: bool,
+    // Shrink or grow the `pixel_size` blocks (or rectangular tiles) to the nearest size that divides
+    // the image evenly, so every block is whole. Width and height are snapped separately.
+    snap_to_grid: bool,
+    // Final output size, applied after quantization with nearest-neighbor scaling.
+    // When only one is given the other follows the source aspect ratio.
+    target_width: Option<u32>,
+    target_height: Option<u32>,
+    // File format of the returned bytes
+    output_format: OutputFormat,
+    // JPEG and AVIF quality, 0-100
+    quality: u8,
+    // Deflate effort for "png" and "png_indexed" output. "fast" (what both encoders do out of the box)
+    // encodes quickest but can leave files several times larger, "default" is much smaller for a
+    // little more time and "best" squeezes out a bit more at a noticeably slower encode.
+    png_compression: PngCompression,
+    // Return the image as a numpy array instead of encoded bytes, `output_format` is ignored then
+    return_array: bool,
+    // Rotate and mirror decoded images as their EXIF orientation says, so phone photos come out upright
+    auto_orient: bool,
+    // Frame of an animated GIF, WebP or PNG input to convert, see `animation::load`
+    frame_index: u32,
+    // Carry the source alpha channel through to the output unchanged, only RGB is quantized
+    preserve_alpha: bool,
+    // Color transparent source pixels are composited over when the alpha channel isn't preserved
+    background_color: Rgb<u8>,
+    // Convert and encode this many rows at a time to bound memory use, see `stream::convert_in_strips`.
+    // Only for per-pixel matching ("none" or "ordered") with a fixed palette and PNG output.
+    strip_height: Option<u32>,
+    // Largest input accepted, by its longest side and its pixel count, checked before it is decoded.
+    // None turns a limit off.
+    max_dimension: Option<u32>,
+    max_pixels: Option<u64>,
+    // Print how long decoding, palette generation, quantization and encoding take to stderr, for this
+    // and every later conversion, see `trace::print_to_stderr`. Needs a build with the "trace" feature,
+    // which records the stages as `tracing` spans whether or not this is set.
+    trace: bool,
+    // Called with the fraction of the conversion done, see `Options::report`
+    progress: Option<progress::Progress>,
+    // Quantized color drawn as transparent in "gif" and "png_indexed" output. The other formats
+    // have no transparent palette entry and ignore it, use `preserve_alpha` for those
+    transparent_color: Option<Rgb<u8>>,
+    // Fill the color table of "gif" and "png_indexed" output (and each frame's of a cycling GIF) up
+    // to this many entries with unused filler, so outputs with different palettes come out uniformly
+    // sized. Other formats ignore it.
+    pad_palette_to: Option<usize>,
+}
+
+impl Options {
+    fn from_kwargs(dithering: bool, kwargs: Option<&PyDict>) -> PyResult<Self> {
+        // The legacy `dithering` flag maps to "floyd_steinberg" / "none" unless `dither_algorithm` is given
+        let mut options = Options {
+            dither_algorithm: if dithering { DitherAlgorithm::FloydSteinberg } else { DitherAlgorithm::None },
+            bayer_size: 4,
+            dither_spread: None,
+            halftone_spacing: 6,
+            halftone_shape: HalftoneShape::Round,
+            dither_kernel: None,
+            serpentine: false,
+            dither_strength: 1.0,
+            dither_clamp: false,
+            dither_clamp_margin: 32.0,
+            predither_noise: 0.0,
+            dither_shadows: None,
+            dither_per_tile: false,
+            palette_preset: None,
+            cga_palette: None,
+            custom_palette: None,
+            palette_mode: PaletteMode::Fixed,
+            preserve_extremes: false,
+            posterize_levels: None,
+            bit_layout: None,
+            threshold: None,
+            duotone: None,
+            foreground: None,
+            background: None,
+            kmeans_iterations: 20,
+            kmeans_tolerance: 0.5,
+            popularity_bits: 8,
+            seed: adaptive::DEFAULT_SEED,
+            distance_metric: DistanceMetric::Rgb,
+            brightness: 0.0,
+            contrast: 1.0,
+            temperature: 0.0,
+            saturation: 1.0,
+            hue_shift: 0.0,
+            sepia: false,
+            sharpen: 0.0,
+            invert: false,
+            linear_rgb: false,
+            recolor: None,
+            scanlines: false,
+            scanline_spacing: 2,
+            scanline_intensity: 0.5,
+            gamma: 1.0,
+            vignette_strength: 0.0,
+            attribute_clash: false,
+            teletext_mosaic: false,
+            c64_multicolor: false,
+            outline: false,
+            outline_color: Rgb([0, 0, 0]),
+            outline_thickness: 1,
+            return_palette: false,
+            lookup_cache: false,
+            kd_tree: false,
+            pixel_size: 1,
+            snap_to_grid: false,
+            wide_pixels: false,
+            tile_width: None,
+            tile_height: None,
+            tile_shape: TileShape::Rectangle,
+            target_width: None,
+            target_height: None,
+            output_format: OutputFormat::Png,
+            quality: 75,
+            png_compression: PngCompression::Fast,
+            return_array: false,
+            auto_orient: true,
+            frame_index: 0,
+            preserve_alpha: false,
+            background_color: Rgb([255, 255, 255]),
+            strip_height: None,
+            max_dimension: Some(DEFAULT_MAX_DIMENSION),
+            max_pixels: Some(DEFAULT_MAX_PIXELS),
+            trace: false,
+            progress: None,
+            transparent_color: None,
+            pad_palette_to: None,
+        };
+        
+        // Applied once the loop is done, whatever order `distance_metric` and its parameters came in
+        let mut hsv_weights = None;
+        let mut chroma_reduction = None;
+        let mut dither_kernel = None;
+        let mut dither_kernel_divisor = None;
+        if let Some(kwargs) = kwargs {
+            for (key, value) in kwargs.iter() {
+                let key: &str = key.extract()?;
+                match key {
+                    "dither_algorithm" => options.dither_algorithm = DitherAlgorithm::from_name(value.extract()?)?,
+                    "bayer_size" => options.bayer_size = value.extract()?,
+                    "dither_spread" => options.dither_spread = match value.extract::<Option<&str>>() {
+                        Ok(None) | Ok(Some("auto")) => None,
+                        Ok(Some(other)) => return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("dither_spread must be a number or 'auto', got '{}'", other))),
+                        Err(_) => Some(value.extract()?),
+                    },
+                    "halftone_spacing" => options.halftone_spacing = value.extract()?,
+                    "dither_kernel" => dither_kernel = Some(value.extract::<Vec<(i32, i32, f32)>>()?),
+                    "dither_kernel_divisor" => dither_kernel_divisor = Some(value.extract::<f32>()?),
+                    "halftone_shape" => options.halftone_shape = HalftoneShape::from_name(value.extract()?)?,
+                    "serpentine" => options.serpentine = value.extract()?,
+                    "dither_strength" => options.dither_strength = value.extract::<f32>()?.clamp(0.0, 1.0),
+                    "dither_clamp" => options.dither_clamp = value.extract()?,
+                    "dither_clamp_margin" => options.dither_clamp_margin = value.extract()?,
+                    "predither_noise" => options.predither_noise = value.extract()?,
+                    "dither_shadows" => options.dither_shadows = value.extract()?,
+                    "dither_per_tile" => options.dither_per_tile = value.extract()?,
+                    "palette_name" => options.palette_preset = Some(palettes::find(value.extract()?)?),
+                    "cga_palette" => options.cga_palette = value.extract()?,
+                    "palette" => options.custom_palette = Some(extract_palette(value)?),
+                    "palette_mode" => options.palette_mode = PaletteMode::from_name(value.extract()?)?,
+                    "preserve_extremes" => options.preserve_extremes = value.extract()?,
+                    "posterize_levels" => options.posterize_levels = value.extract()?,
+                    "bit_layout" => options.bit_layout = Some(BitLayout::from_name(value.extract()?)?),
+                    "threshold" => options.threshold = value.extract()?,
+                    "duotone" => options.duotone = Some(extract_duotone(value)?),
+                    "foreground" => options.foreground = Some(extract_color(value)?),
+                    "background" => options.background = Some(extract_color(value)?),
+                    "kmeans_iterations" => options.kmeans_iterations = value.extract()?,
+                    "kmeans_tolerance" => options.kmeans_tolerance = value.extract()?,
+                    "popularity_bits" => options.popularity_bits = value.extract()?,
+                    "seed" => options.seed = value.extract::<Option<u64>>()?.unwrap_or(adaptive::DEFAULT_SEED),
+                    "distance_metric" => options.distance_metric = DistanceMetric::from_name(value.extract()?)?,
+                    "hsv_weights" => hsv_weights = Some(value.extract::<(f32, f32, f32)>()?),
+                    "chroma_reduction" => chroma_reduction = Some(value.extract::<f32>()?),
+                    "brightness" => options.brightness = value.extract()?,
+                    "contrast" => options.contrast = value.extract()?,
+                    "temperature" => options.temperature = value.extract::<f32>()?.clamp(-1.0, 1.0),
+                    "saturation" => options.saturation = value.extract::<f32>()?.max(0.0),
+                    "hue_shift" => options.hue_shift = value.extract()?,
+                    "sepia" => options.sepia = value.extract()?,
+                    "invert" => options.invert = value.extract()?,
+                    "sharpen" => options.sharpen = value.extract()?,
+                    "linear_rgb" => options.linear_rgb = value.extract()?,
+                    "recolor" => options.recolor = Some(extract_recolor(value)?),
+                    "scanlines" => options.scanlines = value.extract()?,
+                    "scanline_spacing" => options.scanline_spacing = value.extract()?,
+                    "scanline_intensity" => options.scanline_intensity = value.extract::<f32>()?.clamp(0.0, 1.0),
+                    "gamma" => options.gamma = value.extract()?,
+                    "vignette_strength" => options.vignette_strength = value.extract::<f32>()?.clamp(0.0, 1.0),
+                    "attribute_clash" => options.attribute_clash = value.extract()?,
+                    "teletext_mosaic" => options.teletext_mosaic = value.extract()?,
+                    "c64_multicolor" => options.c64_multicolor = value.extract()?,
+                    "outline" => options.outline = value.extract()?,
+                    "outline_color" => options.outline_color = extract_color(value)?,
+                    "outline_thickness" => options.outline_thickness = value.extract()?,
+                    "return_palette" => options.return_palette = value.extract()?,
+                    "lookup_cache" => options.lookup_cache = value.extract()?,
+                    "kd_tree" => options.kd_tree = value.extract()?,
+                    "pixel_size" => options.pixel_size = value.extract()?,
+                    "snap_to_grid" => options.snap_to_grid = value.extract()?,
+                    "wide_pixels" => options.wide_pixels = value.extract()?,
+                    "tile_width" => options.tile_width = value.extract()?,
+                    "tile_height" => options.tile_height = value.extract()?,
+                    "tile_shape" => options.tile_shape = TileShape::from_name(value.extract()?)?,
+                    "target_width" => options.target_width = value.extract()?,
+                    "target_height" => options.target_height = value.extract()?,
+                    "output_format" => options.output_format = OutputFormat::from_name(value.extract()?)?,
+                    "quality" => options.quality = value.extract()?,
+                    "png_compression" => options.png_compression = PngCompression::from_name(value.extract()?)?,
+                    "return_array" => options.return_array = value.extract()?,
+                    "auto_orient" => options.auto_orient = value.extract()?,
+                    "frame_index" => options.frame_index = value.extract()?,
+                    "preserve_alpha" => options.preserve_alpha = value.extract()?,
+                    "background_color" => options.background_color = extract_color(value)?,
+                    "strip_height" => options.strip_height = value.extract()?,
+                    "max_dimension" => options.max_dimension = value.extract()?,
+                    "max_pixels" => options.max_pixels = value.extract()?,
+                    "trace" => options.trace = match value.extract()? {
+                        true if !cfg!(feature = "trace") => return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("trace requires rust_8bit to be built with the 'trace' feature")),
+                        enabled => enabled,
+                    },
+                    "progress" => options.progress = match value {
+                        value if value.is_none() => None,
+                        value if value.is_callable() => Some(progress::Progress::new(value.into())),
+                        _ => return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>("progress must be callable")),
+                    },
+                    "transparent_color" => options.transparent_color = if value.is_none() { None } else { Some(extract_color(value)?) },
+                    "pad_palette_to" => options.pad_palette_to = value.extract()?,
+                    _ => return Err(PyErr::new::<pyo3::exceptions::PyTypeError, _>(format!("Unexpected keyword argument: {}", key))),
+                }
+            }
+        }
+        
+        match dither_kernel {
+            Some(kernel) => {
+                if options.dither_algorithm != DitherAlgorithm::Custom {
+                    return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("dither_kernel requires dither_algorithm='custom'"));
+                }
+                options.dither_kernel = Some(custom_kernel(kernel, dither_kernel_divisor)?);
+            }
+            None if options.dither_algorithm == DitherAlgorithm::Custom => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("dither_algorithm='custom' requires dither_kernel"));
+            }
+            None if dither_kernel_divisor.is_some() => {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("dither_kernel_divisor requires dither_kernel"));
+            }
+            None => {}
+        }
+        
+        if let Some((hue, saturation, value)) = hsv_weights {
+            if !matches!(options.distance_metric, DistanceMetric::Hsv(_)) {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("hsv_weights requires distance_metric='hsv'"));
+            }
+            if hue < 0.0 || saturation < 0.0 || value < 0.0 {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("hsv_weights must not be negative"));
+            }
+            options.distance_metric = DistanceMetric::Hsv([hue, saturation, value]);
+        }
+        if let Some(factor) = chroma_reduction {
+            if !matches!(options.distance_metric, DistanceMetric::YCbCr(_)) {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("chroma_reduction requires distance_metric='ycbcr'"));
+            }
+            if !(factor > 0.0 && factor.is_finite()) {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("chroma_reduction must be a positive number, got {}", factor)));
+            }
+            options.distance_metric = DistanceMetric::YCbCr(factor);
+        }
+        
+        if !(1..=8).contains(&options.popularity_bits) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("popularity_bits must be between 1 and 8, got {}", options.popularity_bits)));
+        }
+        
+        if ![2, 4, 8].contains(&options.bayer_size) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("bayer_size must be 2, 4 or 8, got {}", options.bayer_size)));
+        }
+        if let Some(spread) = options.dither_spread {
+            if !(spread >= 0.0 && spread.is_finite()) {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("dither_spread must be a non-negative number, got {}", spread)));
+            }
+        }
+        if !(options.dither_clamp_margin >= 0.0 && options.dither_clamp_margin.is_finite()) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("dither_clamp_margin must be a non-negative number, got {}", options.dither_clamp_margin)));
+        }
+        if !(options.predither_noise >= 0.0 && options.predither_noise.is_finite()) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("predither_noise must be a non-negative number, got {}", options.predither_noise)));
+        }
+        
+        if options.halftone_spacing < 2 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("halftone_spacing must be at least 2, got {}", options.halftone_spacing)));
+        }
+        
+        if options.custom_palette.is_some() && options.palette_preset.is_some() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("palette and palette_name cannot be used together"));
+        }
+        
+        if options.palette_mode != PaletteMode::Fixed && (options.custom_palette.is_some() || options.palette_preset.is_some()) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("palette_mode cannot be combined with palette or palette_name"));
+        }
+        
+        // Palettes that are given are used as they are, and the half-brite copies have to stay in step
+        if options.preserve_extremes && (options.custom_palette.is_some() || options.palette_preset.is_some() || options.threshold.is_some() || options.palette_mode == PaletteMode::Ehb) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("preserve_extremes cannot be combined with palette, palette_name, threshold or palette_mode 'ehb'"));
+        }
+        
+        if let Some(levels) = options.posterize_levels {
+            // 40 levels is the most whose 64000 combinations still fit in a 16-bit index
+            if !(2..=40).contains(&levels) {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("posterize_levels must be between 2 and 40, got {}", levels)));
+            }
+            if options.custom_palette.is_some() || options.palette_preset.is_some() || options.palette_mode != PaletteMode::Fixed {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("posterize_levels cannot be combined with palette, palette_name or palette_mode"));
+            }
+        }
+        
+        if options.bit_layout.is_some() && (options.custom_palette.is_some() || options.palette_preset.is_some() || options.palette_mode != PaletteMode::Fixed || options.posterize_levels.is_some()) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("bit_layout cannot be combined with palette, palette_name, palette_mode or posterize_levels"));
+        }
+        
+        if options.threshold.is_some() {
+            if options.custom_palette.is_some() || options.palette_preset.is_some() || options.palette_mode != PaletteMode::Fixed || options.posterize_levels.is_some() || options.bit_layout.is_some() {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("threshold cannot be combined with palette, palette_name, palette_mode, posterize_levels or bit_layout"));
+            }
+        } else if options.foreground.is_some() || options.background.is_some() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("foreground and background require threshold"));
+        }
+        
+        if options.duotone.is_some() && (options.custom_palette.is_some() || options.palette_preset.is_some() || options.palette_mode != PaletteMode::Fixed || options.posterize_levels.is_some() || options.bit_layout.is_some() || options.threshold.is_some()) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("duotone cannot be combined with palette, palette_name, palette_mode, posterize_levels, bit_layout or threshold"));
+        }
+        
+        if options.scanline_spacing == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("scanline_spacing must be at least 1"));
+        }
+        
+        if !(options.gamma > 0.0 && options.gamma.is_finite()) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("gamma must be a positive number, got {}", options.gamma)));
+        }
+        options.gamma = options.gamma.clamp(effects::MIN_GAMMA, effects::MAX_GAMMA);
+        if !options.sharpen.is_finite() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("sharpen must be a number, got {}", options.sharpen)));
+        }
+        options.sharpen = options.sharpen.clamp(0.0, adjust::MAX_SHARPEN);
+        
+        if options.outline_thickness == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("outline_thickness must be at least 1"));
+        }
+        
+        if options.pixel_size == 0 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("pixel_size must be at least 1"));
+        }
+        
+        if options.tile_width.is_some() || options.tile_height.is_some() {
+            if options.pixel_size > 1 {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("tile_width and tile_height cannot be combined with pixel_size"));
+            }
+            if options.tile_width == Some(0) || options.tile_height == Some(0) {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("tile_width and tile_height must be at least 1"));
+            }
+        } else if options.tile_shape != TileShape::Rectangle {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("tile_shape requires tile_width or tile_height"));
+        }
+        if options.teletext_mosaic && options.tile_shape != TileShape::Rectangle {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("teletext_mosaic requires rectangular tiles"));
+        }
+        if options.dither_per_tile && !(options.attribute_clash || options.teletext_mosaic || options.c64_multicolor) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("dither_per_tile requires attribute_clash, teletext_mosaic or c64_multicolor"));
+        }
+        // Those cells are only known in output pixels, which error diffusion never sees once the image is resized
+        if options.dither_per_tile && (options.attribute_clash || options.c64_multicolor) && (options.target_width.is_some() || options.target_height.is_some()) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("dither_per_tile with attribute_clash or c64_multicolor cannot be combined with target_width or target_height"));
+        }
+        if options.snap_to_grid && (options.tiling().is_none() || options.tile_shape != TileShape::Rectangle) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("snap_to_grid requires pixel_size or rectangular tiles"));
+        }
+        
+        if options.target_width == Some(0) || options.target_height == Some(0) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("target_width and target_height must be at least 1"));
+        }
+        
+        if options.quality > 100 {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("quality must be between 0 and 100, got {}", options.quality)));
+        }
+        
+        if options.preserve_alpha && !options.return_array && !options.output_format.supports_alpha() {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("preserve_alpha requires output_format 'png', 'webp', 'bmp', 'qoi' or 'avif'"));
+        }
+        
+        if let Some(size) = options.pad_palette_to {
+            if !(1..=256).contains(&size) {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("pad_palette_to must be between 1 and 256, got {}", size)));
+            }
+        }
+        
+        if options.max_dimension == Some(0) || options.max_pixels == Some(0) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("max_dimension and max_pixels must be at least 1"));
+        }
+        
+        if let Some(strip_height) = options.strip_height {
+            if strip_height == 0 {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("strip_height must be at least 1"));
+            }
+            // Error diffusion and halftone cells reach into neighbouring rows, which may be in another strip
+            if !matches!(options.dither_algorithm, DitherAlgorithm::None | DitherAlgorithm::Ordered) {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("strip_height requires dither_algorithm 'none' or 'ordered'"));
+            }
+            if !matches!(options.palette_mode, PaletteMode::Fixed | PaletteMode::Grayscale) {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("strip_height cannot be combined with adaptive palette modes, they need the whole image"));
+            }
+            if options.tiling().is_some() || options.target_width.is_some() || options.target_height.is_some() || options.scanlines || options.vignette_strength > 0.0 || options.attribute_clash || options.teletext_mosaic || options.c64_multicolor || options.outline || options.sharpen > 0.0 || options.preserve_alpha || options.return_array {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("strip_height cannot be combined with pixel_size, tile_width, tile_height, wide_pixels, target_width, target_height, scanlines, vignette_strength, attribute_clash, teletext_mosaic, c64_multicolor, outline, sharpen, preserve_alpha or return_array"));
+            }
+            if !matches!(options.output_format, OutputFormat::Png | OutputFormat::PngIndexed) {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("strip_height requires output_format 'png' or 'png_indexed'"));
+            }
+        }
+        
+        if let Some(cga_palette) = options.cga_palette {
+            if options.palette_preset.map(|preset| preset.name) != Some("cga") {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("cga_palette requires palette_name='cga'"));
+            }
+            if cga_palette > 1 {
+                return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("cga_palette must be 0 or 1, got {}", cga_palette)));
+            }
+        }
+        
+        Ok(options)
+    }
+}
+
+impl Options {
+    // Tell the `progress` callback how far the conversion has got. Calls are kept to a few dozen
+    // per image because each one has to take the GIL back, without a callback this does nothing.
+    fn report(&self, fraction: f32) {
+        if let Some(progress) = &self.progress {
+            progress.report(fraction);
+        }
+    }
+    
+    // Raise whatever the `progress` callback raised
+    fn check_progress(&self) -> PyResult<()> {
+        self.progress.as_ref().map_or(Ok(()), |progress| progress.check())
+    }
+    
+    // Refuse an image of `width` x `height` if it is over `max_dimension` or `max_pixels`
+    fn check_size(&self, width: u32, height: u32) -> PyResult<()> {
+        let too_large = |limit: String| Err(PyErr::new::<errors::ImageTooLargeError, _>(format!("Image is {}x{}, larger than {}. Pass a higher limit or None to convert it anyway.", width, height, limit)));
+        if let Some(max) = self.max_dimension.filter(|&max| width.max(height) > max) {
+            return too_large(format!("max_dimension={}", max));
+        }
+        if let Some(max) = self.max_pixels.filter(|&max| width as u64 * height as u64 > max) {
+            return too_large(format!("max_pixels={}", max));
+        }
+        Ok(())
+    }
+    
+    // Trace a pipeline stage until the returned guard is dropped, see `trace::Stage`
+    fn stage(&self, name: &'static str) -> trace::Stage {
+        #[cfg(feature = "trace")]
+        if self.trace {
+            trace::print_to_stderr();
+        }
+        trace::Stage::start(name)
+    }
+    
+    // Cells error diffusion stays within under `dither_per_tile`, in pixels of the image being dithered:
+    // the 2x3 mosaic cells of a Teletext character, or the 8x8 attribute (or C64 multicolor) cells counted
+    // in `pixel_size` squares or tiles, which only line up exactly when those divide 8
+    fn dither_cell(&self) -> Option<(u32, u32)> {
+        if !self.dither_per_tile {
+            return None;
+        }
+        if self.teletext_mosaic {
+            return Some(effects::TELETEXT_SIXELS);
+        }
+        let (width, height) = self.tiling().map_or((1, 1), |tiling| (tiling.width, tiling.height));
+        Some(((effects::ATTRIBUTE_CELL.0 / width).max(1), (effects::ATTRIBUTE_CELL.1 / height).max(1)))
+    }
+    
+    // Cells the image is averaged into before quantization, `None` to keep full resolution
+    fn tiling(&self) -> Option<Tiling> {
+        let tiling = match (self.tile_width, self.tile_height) {
+            (None, None) if self.pixel_size > 1 => Some(Tiling::square(self.pixel_size)),
+            (None, None) => None,
+            (width, height) => {
+                let width = width.or(height).unwrap_or(1);
+                let height = height.unwrap_or(width);
+                let tiling = Tiling { width, height, shape: self.tile_shape };
+                (width > 1 || height > 1 || self.tile_shape != TileShape::Rectangle).then_some(tiling)
+            }
+        };
+        if !self.wide_pixels {
+            return tiling;
+        }
+        let tiling = tiling.unwrap_or(Tiling::square(1));
+        Some(Tiling { width: tiling.width * 2, ..tiling })
+    }
+}
+
+// Convert a Python (r, g, b) sequence into a color
+fn extract_color(value: &PyAny) -> PyResult<Rgb<u8>> {
+    let channels: Vec<i64> = value.extract()
+        .map_err(|_| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Expected an (r, g, b) color, got {}", value)))?;
+    if channels.len() != 3 || channels.iter().any(|c| !(0..=255).contains(c)) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Color must be three values in 0-255, got {}", value)));
+    }
+    Ok(Rgb([channels[0] as u8, channels[1] as u8, channels[2] as u8]))
+}
+
+// Convert a `palette_size` argument: a number of colors, or "auto" (the same as 0) to let
+// `adaptive::estimate_size` choose one for the image
+fn extract_palette_size(value: &PyAny) -> PyResult<usize> {
+    match value.extract::<&str>() {
+        Ok("auto") => Ok(AUTO_PALETTE_SIZE),
+        Ok(other) => Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("palette_size must be a number or 'auto', got '{}'", other))),
+        Err(_) => value.extract(),
+    }
+}
+
+// Check a `dither_kernel` and divide its weights by `divisor`, or by their sum when none is given.
+// Error can only go to pixels that haven't been visited, to the right on the same row or onto later rows.
+fn custom_kernel(kernel: Vec<(i32, i32, f32)>, divisor: Option<f32>) -> PyResult<Vec<(i32, i32, f32)>> {
+    let invalid = |message: String| Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(message));
+    if kernel.is_empty() {
+        return invalid("dither_kernel must have at least one entry".to_string());
+    }
+    for &(dx, dy, weight) in &kernel {
+        if dy < 0 || (dy == 0 && dx <= 0) {
+            return invalid(format!("dither_kernel offset ({}, {}) points at a pixel that is already dithered, entries must have dy > 0, or dy == 0 and dx > 0", dx, dy));
+        }
+        if !(weight >= 0.0 && weight.is_finite()) {
+            return invalid(format!("dither_kernel weights must be non-negative numbers, got {}", weight));
+        }
+    }
+    let divisor = divisor.unwrap_or_else(|| kernel.iter().map(|&(_, _, weight)| weight).sum());
+    if !(divisor > 0.0 && divisor.is_finite()) {
+        return invalid(format!("dither_kernel_divisor must be a positive number, got {}", divisor));
+    }
+    Ok(kernel.into_iter().map(|(dx, dy, weight)| (dx, dy, weight / divisor)).collect())
+}
+
+// Convert a Python (shadow, highlight) pair of (r, g, b) colors for `duotone`
+fn extract_duotone(value: &PyAny) -> PyResult<(Rgb<u8>, Rgb<u8>)> {
+    let (shadow, highlight): (&PyAny, &PyAny) = value.extract()
+        .map_err(|_| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("duotone must be a (shadow, highlight) pair of (r, g, b) colors, got {}", value)))?;
+    Ok((extract_color(shadow)?, extract_color(highlight)?))
+}
+
+// Convert a Python dict of (r, g, b) -> (r, g, b) into a color replacement map
+fn extract_recolor(value: &PyAny) -> PyResult<HashMap<Rgb<u8>, Rgb<u8>>> {
+    let mapping: &PyDict = value.downcast()
+        .map_err(|_| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("recolor must be a dict mapping (r, g, b) to (r, g, b), got {}", value)))?;
+    mapping.iter().map(|(source, target)| Ok((extract_color(source)?, extract_color(target)?))).collect()
+}
+
+// Convert a Python list of (r, g, b) colors into a palette
+fn extract_palette(value: &PyAny) -> PyResult<Vec<Rgb<u8>>> {
+    let entries: Vec<&PyAny> = value.extract()?;
+    if entries.is_empty() {
+        return Err(PyErr::new::<errors::InvalidPaletteError, _>("palette must contain at least one color"));
+    }
+    // Pixels are tracked as 16-bit palette indices
+    if entries.len() > MAX_PALETTE_COLORS {
+        return Err(PyErr::new::<errors::InvalidPaletteError, _>(format!("palette must contain at most {} colors, got {}", MAX_PALETTE_COLORS, entries.len())));
+    }
+    entries.into_iter()
+        .map(|entry| extract_color(entry).map_err(|e| PyErr::new::<errors::InvalidPaletteError, _>(e.value(value.py()).to_string())))
+        .collect()
+}
+
+// numpy's C API is loaded lazily and panics if numpy is missing, import it up front so that is an ImportError instead
+fn require_numpy(py: Python<'_>) -> PyResult<()> {
+    py.import("numpy")?;
+    Ok(())
+}
+
+// Split a (height, width, 3 or 4) uint8 numpy array into an RGB image and, for 4 channels, its alpha
+fn extract_array(array: &PyAny) -> PyResult<SourceImage> {
+    require_numpy(array.py())?;
+    let array: &PyArrayDyn<u8> = array.downcast()
+        .map_err(|_| PyErr::new::<pyo3::exceptions::PyValueError, _>("Expected a numpy array with dtype uint8"))?;
+    if !array.is_c_contiguous() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("Array must be C-contiguous, use numpy.ascontiguousarray"));
+    }
+    let (height, width, channels) = match *array.shape() {
+        [height, width, channels @ (3 | 4)] => (height as u32, width as u32, channels),
+        ref shape => return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Array must have shape (height, width, 3) or (height, width, 4), got {:?}", shape))),
+    };
+    let data = array.to_vec()
+        .map_err(|e| PyErr::new::<errors::DecodeError, _>(format!("Failed to read array: {}", e)))?;
+    
+    if channels == 3 {
+        let rgb = ImageBuffer::from_raw(width, height, data)
+            .ok_or_else(|| PyErr::new::<errors::DecodeError, _>("Array data doesn't match its shape"))?;
+        return Ok(SourceImage { rgb, alpha: None, detail: None });
+    }
+    let rgb = ImageBuffer::from_fn(width, height, |x, y| {
+        let i = (y as usize * width as usize + x as usize) * 4;
+        Rgb([data[i], data[i + 1], data[i + 2]])
+    });
+    let alpha = ImageBuffer::from_fn(width, height, |x, y| Luma([data[(y as usize * width as usize + x as usize) * 4 + 3]]));
+    Ok(SourceImage { rgb, alpha: Some(alpha), detail: None })
+}
+
+#[pyfunction]
+#[pyo3(signature = (image_data, palette_size, dithering, **kwargs))]
+fn convert_to_8bit(py: Python<'_>, image_data: &[u8], #[pyo3(from_py_with = "extract_palette_size")] palette_size: usize, dithering: bool, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+    let options = Options::from_kwargs(dithering, kwargs)?;
+    if options.strip_height.is_some() {
+        let (encoded, palette) = py.allow_threads(|| run_in_strips(image_data, palette_size, &options))?;
+        return Ok(strips_into_python(py, &encoded, &palette, &options));
+    }
+    
+    // Decoding, quantizing and encoding don't touch Python objects, so let other threads run meanwhile
+    let (quantized, encoded) = py.allow_threads(|| run_pipeline(decode(image_data, &options)?, palette_size, &options))?;
+    
+    into_python(py, quantized, encoded, &options)
+}
+
+// Same as `convert_to_8bit`, but returns `(result, metadata)`: `result` is what `convert_to_8bit` would
+// return, and `metadata` a dict with the output "width" and "height", the "palette_size" of the final
+// palette and "colors_used", how many of its distinct colors actually appear in the output
+#[pyfunction]
+#[pyo3(signature = (image_data, palette_size, dithering, **kwargs))]
+fn convert_with_metadata(py: Python<'_>, image_data: &[u8], #[pyo3(from_py_with = "extract_palette_size")] palette_size: usize, dithering: bool, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+    let options = Options::from_kwargs(dithering, kwargs)?;
+    if options.strip_height.is_some() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("strip_height is not supported by convert_with_metadata"));
+    }
+    
+    let (quantized, encoded, colors_used) = py.allow_threads(|| {
+        let (quantized, encoded) = run_pipeline(decode(image_data, &options)?, palette_size, &options)?;
+        let colors_used = colors_used(&quantized);
+        Ok::<_, PyErr>((quantized, encoded, colors_used))
+    })?;
+    
+    let metadata = PyDict::new(py);
+    metadata.set_item("width", quantized.image.width())?;
+    metadata.set_item("height", quantized.image.height())?;
+    metadata.set_item("palette_size", quantized.palette.len())?;
+    metadata.set_item("colors_used", colors_used)?;
+    Ok((into_python(py, quantized, encoded, &options)?, metadata).into_py(py))
+}
+
+// Same as `convert_to_8bit`, but returns `(result, palette, indices)`: `result` is what `convert_to_8bit`
+// would return, `palette` the final (r, g, b) colors and `indices` a (height, width) uint8 numpy array of
+// the palette position chosen for every pixel, for tile maps or editing the image at the index level
+#[pyfunction]
+#[pyo3(signature = (image_data, palette_size, dithering, **kwargs))]
+fn convert_with_indices(py: Python<'_>, image_data: &[u8], #[pyo3(from_py_with = "extract_palette_size")] palette_size: usize, dithering: bool, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+    let options = Options::from_kwargs(dithering, kwargs)?;
+    if options.strip_height.is_some() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("strip_height is not supported by convert_with_indices"));
+    }
+    require_numpy(py)?;
+    
+    let (quantized, encoded) = py.allow_threads(|| run_pipeline(decode(image_data, &options)?, palette_size, &options))?;
+    
+    let palette = palette_colors(&quantized.palette);
+    let indices = index_array(py, &quantized)?;
+    Ok((into_python(py, quantized, encoded, &options)?, palette, indices).into_py(py))
+}
+
+// Copy the palette positions into a (height, width) uint8 numpy array
+fn index_array(py: Python<'_>, quantized: &Quantized) -> PyResult<PyObject> {
+    if quantized.palette.len() > 256 {
+        return Err(PyErr::new::<errors::EncodeError, _>(format!("Index arrays support at most 256 colors, the palette has {}", quantized.palette.len())));
+    }
+    if let Some(index) = quantized.indices.pixels().map(|index| index[0]).find(|&index| index as usize >= quantized.palette.len()) {
+        return Err(PyErr::new::<errors::Pixel8BitError, _>(format!("Palette index {} is out of range for a palette of {} colors", index, quantized.palette.len())));
+    }
+    let (width, height) = quantized.indices.dimensions();
+    let data = quantized.indices.as_raw().iter().map(|&index| index as u8).collect();
+    let array = Array2::from_shape_vec((height as usize, width as usize), data)
+        .map_err(|e| PyErr::new::<errors::EncodeError, _>(format!("Failed to build array: {}", e)))?;
+    Ok(array.into_pyarray(py).into())
+}
+
+// Number of distinct colors in the output, counting palette entries that came out the same color once
+fn colors_used(quantized: &Quantized) -> usize {
+    let mut used = vec![false; quantized.palette.len()];
+    for index in quantized.indices.pixels() {
+        used[index[0] as usize] = true;
+    }
+    let colors: HashSet<Rgb<u8>> = quantized.palette.iter().zip(&used).filter(|(_, &used)| used).map(|(&color, _)| color).collect();
+    colors.len()
+}
+
+// Same as `convert_to_8bit`, but takes an already decoded (height, width, 3 or 4) uint8 numpy array
+#[pyfunction]
+#[pyo3(signature = (array, palette_size, dithering, **kwargs))]
+fn convert_array_to_8bit(py: Python<'_>, array: &PyAny, #[pyo3(from_py_with = "extract_palette_size")] palette_size: usize, dithering: bool, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+    let options = Options::from_kwargs(dithering, kwargs)?;
+    if options.strip_height.is_some() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("strip_height is only supported for encoded images, the array is already in memory"));
+    }
+    let mut source = extract_array(array)?;
+    options.check_size(source.rgb.width(), source.rgb.height())?;
+    
+    // Without `preserve_alpha` a 4-channel array is composited over the background, like decoded images
+    source.alpha = match (options.preserve_alpha, source.alpha) {
+        (true, Some(alpha)) => Some(alpha),
+        (true, None) => Some(ImageBuffer::from_pixel(source.rgb.width(), source.rgb.height(), Luma([255]))),
+        (false, Some(alpha)) => {
+            let rgba = ImageBuffer::from_fn(source.rgb.width(), source.rgb.height(), |x, y| {
+                let [r, g, b] = source.rgb.get_pixel(x, y).0;
+                Rgba([r, g, b, alpha.get_pixel(x, y)[0]])
+            });
+            source.rgb = flatten(DynamicImage::ImageRgba8(rgba), options.background_color).to_rgb8();
+            None
+        }
+        (false, None) => None,
+    };
+    let (quantized, encoded) = py.allow_threads(|| run_pipeline(source, palette_size, &options))?;
+    
+    into_python(py, quantized, encoded, &options)
+}
+
+// Convert a list of encoded images with the same settings, returning one result per image in order.
+// Images are processed in parallel with the GIL released. If any fail, the error names the first failing index.
+#[pyfunction]
+#[pyo3(signature = (images, palette_size, dithering, **kwargs))]
+fn convert_batch_to_8bit(py: Python<'_>, images: Vec<&[u8]>, #[pyo3(from_py_with = "extract_palette_size")] palette_size: usize, dithering: bool, kwargs: Option<&PyDict>) -> PyResult<Vec<PyObject>> {
+    let options = Options::from_kwargs(dithering, kwargs)?;
+    if options.progress.is_some() || options.strip_height.is_some() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("progress and strip_height are not supported for batch conversion"));
+    }
+    
+    let results: Vec<PyResult<(Quantized, Option<Vec<u8>>)>> = py.allow_threads(|| {
+        images.par_iter()
+            .map(|image_data| run_pipeline(decode(image_data, &options)?, palette_size, &options))
+            .collect()
+    });
+    
+    results.into_iter()
+        .enumerate()
+        .map(|(index, result)| {
+            // Keep the exception class so callers can still tell decode from encode failures
+            let (quantized, encoded) = result
+                .map_err(|e| PyErr::from_type(e.get_type(py), format!("Image {} failed: {}", index, e.value(py))))?;
+            into_python(py, quantized, encoded, &options)
+        })
+        .collect()
+}
+
+// Same as `convert_to_8bit`, but reads the image from `path`. With `output_path` the result is written
+// there instead of being returned, and the call returns `None`, or just the palette with `return_palette`.
+#[pyfunction]
+#[pyo3(signature = (path, palette_size, dithering, output_path=None, **kwargs))]
+fn convert_file_to_8bit(py: Python<'_>, path: &str, #[pyo3(from_py_with = "extract_palette_size")] palette_size: usize, dithering: bool, output_path: Option<&str>, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+    let options = Options::from_kwargs(dithering, kwargs)?;
+    if output_path.is_some() && options.return_array {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("return_array cannot be combined with output_path"));
+    }
+    
+    if options.strip_height.is_some() {
+        let (encoded, palette) = py.allow_threads(|| {
+            let image_data = std::fs::read(path).map_err(|e| io_error("read", path, e))?;
+            let (encoded, palette) = run_in_strips(&image_data, palette_size, &options)?;
+            if let Some(output_path) = output_path {
+                std::fs::write(output_path, &encoded).map_err(|e| io_error("write", output_path, e))?;
+            }
+            Ok::<_, PyErr>((encoded, palette))
+        })?;
+        return Ok(match output_path {
+            Some(_) if options.return_palette => palette_colors(&palette).into_py(py),
+            Some(_) => py.None(),
+            None => strips_into_python(py, &encoded, &palette, &options),
+        });
+    }
+    
+    let (quantized, encoded) = py.allow_threads(|| {
+        let image_data = std::fs::read(path).map_err(|e| io_error("read", path, e))?;
+        let (quantized, encoded) = run_pipeline(decode(&image_data, &options)?, palette_size, &options)?;
+        match (output_path, encoded) {
+            (Some(output_path), Some(output_bytes)) => {
+                std::fs::write(output_path, output_bytes).map_err(|e| io_error("write", output_path, e))?;
+                Ok::<_, PyErr>((quantized, None))
+            }
+            (_, encoded) => Ok((quantized, encoded)),
+        }
+    })?;
+    
+    if output_path.is_none() {
+        return into_python(py, quantized, encoded, &options);
+    }
+    if options.return_palette {
+        Ok(palette_colors(&quantized.palette).into_py(py))
+    } else {
+        Ok(py.None())
+    }
+}
+
+// Turn a file system error into the matching Python OSError subclass, naming the file
+fn io_error(action: &str, path: &str, error: std::io::Error) -> PyErr {
+    let message = format!("Failed to {} {}: {}", action, path, error);
+    match error.kind() {
+        std::io::ErrorKind::NotFound => PyErr::new::<pyo3::exceptions::PyFileNotFoundError, _>(message),
+        std::io::ErrorKind::PermissionDenied => PyErr::new::<pyo3::exceptions::PyPermissionError, _>(message),
+        _ => PyErr::new::<pyo3::exceptions::PyOSError, _>(message),
+    }
+}
+
+// Convert an image and write it as an animated GIF that cycles palette entries `cycle_start`..=`cycle_end`,
+// rotating them one step per frame. `frame_delay` is in milliseconds, GIF stores it in hundredths of a second.
+#[pyfunction]
+#[pyo3(signature = (image_data, palette_size, dithering, frames, cycle_start, cycle_end, frame_delay=100, **kwargs))]
+#[allow(clippy::too_many_arguments)]
+fn convert_to_cycling_gif(py: Python<'_>, image_data: &[u8], #[pyo3(from_py_with = "extract_palette_size")] palette_size: usize, dithering: bool, frames: usize, cycle_start: usize, cycle_end: usize, frame_delay: u32, kwargs: Option<&PyDict>) -> PyResult<PyObject> {
+    let options = Options::from_kwargs(dithering, kwargs)?;
+    if options.return_array || options.strip_height.is_some() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("return_array and strip_height are not supported for cycling GIFs"));
+    }
+    let cycle = encode::Cycle {
+        frames,
+        start: cycle_start,
+        end: cycle_end,
+        delay: (frame_delay / 10).min(u16::MAX as u32) as u16,
+    };
+    
+    let (quantized, encoded) = py.allow_threads(|| {
+        let quantized = convert_image(decode(image_data, &options)?, palette_size, &options)?;
+        let encoded = encode::encode_cycling_gif(&quantized, options.transparent_color, options.pad_palette_to, &cycle)?;
+        Ok::<_, PyErr>((quantized, Some(encoded)))
+    })?;
+    
+    into_python(py, quantized, encoded, &options)
+}
+
+// Return the `n` most representative colors of an image, most common first, without converting it.
+// `method` is "median_cut", "kmeans", "octree" or "popularity". With `exact` off, channels are first reduced to
+// 5 bits so near-identical shades count together and large photos build the palette faster.
+#[pyfunction]
+#[pyo3(name = "extract_palette", signature = (image_data, n, method="median_cut", exact=true))]
+fn extract_dominant_colors(py: Python<'_>, image_data: &[u8], n: usize, method: &str, exact: bool) -> PyResult<Vec<(u8, u8, u8)>> {
+    let mode = PaletteMode::from_name(method).ok().filter(|mode| matches!(mode, PaletteMode::MedianCut | PaletteMode::KMeans | PaletteMode::Octree | PaletteMode::Popularity));
+    let Some(mode) = mode else {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("method must be 'median_cut', 'kmeans', 'octree' or 'popularity', got '{}'", method)));
+    };
+    if n == 0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("n must be at least 1"));
+    }
+    let options = Options::from_kwargs(false, None)?;
+    
+    let colors = py.allow_threads(|| {
+        let mut img = decode(image_data, &options)?.rgb;
+        if !exact {
+            // Keep the top 5 bits and move to the middle of the dropped range
+            img.iter_mut().for_each(|c| *c = (*c & 0xF8) | 0x04);
+        }
+        
+        let size = n.min(256);
+        let palette = match mode {
+            PaletteMode::KMeans => adaptive::kmeans(&img, size, 20, 0.5, adaptive::DEFAULT_SEED),
+            PaletteMode::Octree => adaptive::octree(&img, size),
+            PaletteMode::Popularity => adaptive::popularity(&img, size, 8),
+            _ => adaptive::median_cut(&img, size),
+        };
+        Ok::<_, PyErr>(adaptive::by_population(&img, palette))
+    })?;
+    
+    Ok(colors.iter().map(|c| (c[0], c[1], c[2])).collect())
+}
+
+// Render an image as ASCII art `cols` characters wide, picking each character by the brightness of
+// its block from `charset` (dark to light, `ascii::DEFAULT_RAMP` when not given)
+#[pyfunction]
+#[pyo3(signature = (image_data, cols, charset=None))]
+fn convert_to_ascii(py: Python<'_>, image_data: &[u8], cols: u32, charset: Option<&str>) -> PyResult<String> {
+    if cols == 0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("cols must be at least 1"));
+    }
+    let ramp: Vec<char> = charset.unwrap_or(ascii::DEFAULT_RAMP).chars().collect();
+    if ramp.is_empty() {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("charset must contain at least one character"));
+    }
+    let options = Options::from_kwargs(false, None)?;
+    
+    py.allow_threads(|| {
+        let source = decode(image_data, &options)?;
+        // Columns beyond the image width stretch it, keep that within what a conversion would accept
+        let (width, height) = source.rgb.dimensions();
+        let fits = |(w, h): (u32, u32)| w.max(h) <= DEFAULT_MAX_DIMENSION && w as u64 * h as u64 <= DEFAULT_MAX_PIXELS;
+        if !ascii::stretched_size(width, height, cols).is_some_and(fits) {
+            return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("cols={} is too many for a {}x{} image, it would be stretched past {} pixels across or {} in total", cols, width, height, DEFAULT_MAX_DIMENSION, DEFAULT_MAX_PIXELS)));
+        }
+        Ok(ascii::render(&source.rgb, cols, &ramp))
+    })
+}
+
+// Render a palette as a PNG of `swatch_size` pixel squares, `columns` to a row (enough for a square
+// grid when not given) in palette order. `palette` is a list of (r, g, b) colors or the name of a
+// preset. Cells left over in the last row are transparent.
 #[pyfunction]
-fn convert_to_8bit(image_data: &[u8], palette_size: usize, dithering: bool) -> PyResult<Py<PyBytes>> {
+#[pyo3(signature = (palette, swatch_size=16, columns=None))]
+fn render_palette_swatch(py: Python<'_>, palette: &PyAny, swatch_size: u32, columns: Option<u32>) -> PyResult<PyObject> {
+    let colors = match palette.extract::<&str>() {
+        Ok(name) => palettes::find(name)?.colors.to_vec(),
+        Err(_) => extract_palette(palette)?,
+    };
+    if swatch_size == 0 {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("swatch_size must be at least 1"));
+    }
+    if columns == Some(0) {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("columns must be at least 1"));
+    }
+    let count = colors.len() as u32;
+    let columns = columns.unwrap_or_else(|| (count as f64).sqrt().ceil() as u32).min(count);
+    let rows = count.div_ceil(columns);
+    let (width, height) = (columns.saturating_mul(swatch_size), rows.saturating_mul(swatch_size));
+    if width.max(height) > DEFAULT_MAX_DIMENSION {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Swatch image would be {}x{}, larger than {} pixels across", width, height, DEFAULT_MAX_DIMENSION)));
+    }
+    let options = Options::from_kwargs(false, None)?;
+    
+    let encoded = py.allow_threads(|| {
+        let cell = |x: u32, y: u32| (y / swatch_size) * columns + x / swatch_size;
+        let indices: IndexImage = ImageBuffer::from_fn(width, height, |x, y| Luma([cell(x, y).min(count - 1) as u16]));
+        let image = ImageBuffer::from_fn(width, height, |x, y| colors[indices.get_pixel(x, y)[0] as usize]);
+        let alpha = (!count.is_multiple_of(columns)).then(|| ImageBuffer::from_fn(width, height, |x, y| Luma([if cell(x, y) < count { 255 } else { 0 }])));
+        encode::encode(&Quantized { image, indices, palette: colors, alpha }, &options)
+    })?;
+    Ok(PyBytes::new(py, &encoded).into())
+}
+
+// Decode image bytes, splitting off the alpha channel when it is to be preserved
+fn decode(image_data: &[u8], options: &Options) -> PyResult<SourceImage> {
+    let _stage = options.stage("decode");
+    check_header_size(image_data, options)?;
     // Load image from bytes
-    let img = image::load_from_memory(image_data)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Failed to load image: {}", e)))?;
+    let img = animation::load(image_data, options.frame_index)?;
+    let img = if options.auto_orient { orient::auto_orient(img, image_data) } else { img };
+    
+    let alpha = options.preserve_alpha.then(|| alpha_channel(&img));
+    let img = if options.preserve_alpha { img } else { flatten(img, options.background_color) };
+    let rgb = img.to_rgb8();
+    let detail = high_bit_depth(&img).then(|| sub_byte_detail(&img, &rgb));
+    Ok(SourceImage { rgb, alpha, detail })
+}
+
+// Check the size an image's header gives against the limits in `options`, before any pixel is decoded.
+// Headers that can't be read are left for the decoder to report.
+fn check_header_size(image_data: &[u8], options: &Options) -> PyResult<()> {
+    let reader = image::io::Reader::new(std::io::Cursor::new(image_data)).with_guessed_format();
+    match reader.ok().and_then(|reader| reader.into_dimensions().ok()) {
+        Some((width, height)) => options.check_size(width, height),
+        None => Ok(()),
+    }
+}
+
+// Convert an image and encode it, unless the result goes back as an array
+fn run_pipeline(source: SourceImage, palette_size: usize, options: &Options) -> PyResult<(Quantized, Option<Vec<u8>>)> {
+    let quantized = convert_image(source, palette_size, options)?;
+    
+    // Convert output image to bytes
+    let encoded = if options.return_array {
+        None
+    } else {
+        let _stage = options.stage("encode");
+        Some(encode::encode(&quantized, options)?)
+    };
+    
+    Ok((quantized, encoded))
+}
+
+// Build the value returned to Python from a conversion result and its encoded bytes
+fn into_python(py: Python<'_>, quantized: Quantized, encoded: Option<Vec<u8>>, options: &Options) -> PyResult<PyObject> {
+    let output: PyObject = match encoded {
+        Some(output_bytes) => PyBytes::new(py, &output_bytes).into(),
+        None => to_array(py, &quantized)?,
+    };
+    
+    if options.return_palette {
+        Ok((output, palette_colors(&quantized.palette)).into_py(py))
+    } else {
+        Ok(output)
+    }
+}
+
+// Decode image bytes and convert them strip by strip, see `stream::convert_in_strips`
+fn run_in_strips(image_data: &[u8], palette_size: usize, options: &Options) -> PyResult<(Vec<u8>, Vec<Rgb<u8>>)> {
+    let img = {
+        let _stage = options.stage("decode");
+        check_header_size(image_data, options)?;
+        let img = animation::load(image_data, options.frame_index)?;
+        let img = if options.auto_orient { orient::auto_orient(img, image_data) } else { img };
+        flatten(img, options.background_color)
+    };
+    // Strips are matched and encoded together, so they are timed as one stage
+    let _stage = options.stage("convert and encode strips");
+    stream::convert_in_strips(&img, palette_size, options)
+}
+
+// Same as `into_python` for the bytes and palette of a strip by strip conversion
+fn strips_into_python(py: Python<'_>, encoded: &[u8], palette: &[Rgb<u8>], options: &Options) -> PyObject {
+    let output: PyObject = PyBytes::new(py, encoded).into();
+    if options.return_palette {
+        (output, palette_colors(palette)).into_py(py)
+    } else {
+        output
+    }
+}
+
+// The palette as (r, g, b) tuples for Python
+fn palette_colors(palette: &[Rgb<u8>]) -> Vec<(u8, u8, u8)> {
+    palette.iter().map(|c| (c[0], c[1], c[2])).collect()
+}
+
+// Copy the quantized image into a (height, width, 3) uint8 numpy array, (height, width, 4) when alpha was kept
+fn to_array(py: Python<'_>, quantized: &Quantized) -> PyResult<PyObject> {
+    require_numpy(py)?;
+    let (width, height) = quantized.image.dimensions();
+    let (data, channels) = match &quantized.alpha {
+        Some(alpha) => {
+            let data = quantized.image.pixels().zip(alpha.pixels())
+                .flat_map(|(color, opacity)| [color[0], color[1], color[2], opacity[0]])
+                .collect();
+            (data, 4)
+        }
+        None => (quantized.image.as_raw().clone(), 3),
+    };
+    let array = Array3::from_shape_vec((height as usize, width as usize, channels), data)
+        .map_err(|e| PyErr::new::<errors::EncodeError, _>(format!("Failed to build array: {}", e)))?;
+    Ok(array.into_pyarray(py).into())
+}
+
+// Whether the decoded image stores more than 8 bits per channel (16-bit or float)
+fn high_bit_depth(img: &image::DynamicImage) -> bool {
+    let color = img.color();
+    color.bytes_per_pixel() > color.channel_count()
+}
+
+// What `to_rgb8` rounded away: per pixel, the 16-bit value in 0-255 units minus the 8-bit value
+fn sub_byte_detail(img: &image::DynamicImage, rgb: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Vec<[f32; 3]> {
+    img.to_rgb16().pixels().zip(rgb.pixels())
+        .map(|(wide, narrow)| [0, 1, 2].map(|c| wide[c] as f32 / 257.0 - narrow[c] as f32))
+        .collect()
+}
+
+// Composite an image with an alpha channel over a solid `background`, so transparent pixels get a
+// defined color instead of whatever was stored under them. 16-bit images stay 16-bit.
+fn flatten(img: DynamicImage, background: Rgb<u8>) -> DynamicImage {
+    if !img.color().has_alpha() {
+        return img;
+    }
+    let over = |color: [f32; 3], alpha: f32, scale: f32| [0, 1, 2].map(|c| color[c] * alpha + background[c] as f32 / 255.0 * scale * (1.0 - alpha));
+    if high_bit_depth(&img) {
+        let rgba = img.to_rgba16();
+        DynamicImage::ImageRgb16(ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+            let p = rgba.get_pixel(x, y);
+            Rgb(over([p[0] as f32, p[1] as f32, p[2] as f32], p[3] as f32 / 65535.0, 65535.0).map(|c| c.round() as u16))
+        }))
+    } else {
+        let rgba = img.to_rgba8();
+        DynamicImage::ImageRgb8(ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+            let p = rgba.get_pixel(x, y);
+            Rgb(over([p[0] as f32, p[1] as f32, p[2] as f32], p[3] as f32 / 255.0, 255.0).map(|c| c.round() as u8))
+        }))
+    }
+}
+
+// Split out the alpha channel of a decoded image, fully opaque if it has none
+fn alpha_channel(img: &image::DynamicImage) -> AlphaImage {
+    let rgba = img.to_rgba8();
+    ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| Luma([rgba.get_pixel(x, y)[3]]))
+}
+
+// A decoded image, split into the colors that get quantized and the alpha that is carried along
+struct SourceImage {
+    rgb: ImageBuffer<Rgb<u8>, Vec<u8>>,
+    alpha: Option<AlphaImage>,
+    // Fraction of a level lost when a high bit depth source was narrowed to `rgb`, see `sub_byte_detail`.
+    // Error diffusion adds it back so smooth 16-bit gradients dither without 8-bit banding.
+    detail: Option<Vec<[f32; 3]>>,
+}
+
+// Run the full conversion pipeline on a decoded image
+fn convert_image(source: SourceImage, palette_size: usize, options: &Options) -> PyResult<Quantized> {
+    let SourceImage { rgb: mut rgb_img, alpha, detail } = source;
+    let (width, height) = rgb_img.dimensions();
+    options.report(0.0);
+    // Adjustments work on 8-bit values, the extra source precision only applies to an untouched image
+    let detail = detail.filter(|_| adjust::is_identity(options) && options.sharpen == 0.0);
+    adjust::adjust(&mut rgb_img, options);
+    
+    let tiling = options.tiling().map(|tiling| if options.snap_to_grid { tiling.snapped(width, height) } else { tiling });
+    
+    // Chunky pixels: quantize one averaged color per cell, then scale the cells back up
+    let mut quantized = if let Some(tiling) = tiling {
+        let mut small = pixelate::downsample(&rgb_img, tiling, options.linear_rgb);
+        // On the cells, averaging them would undo most of the sharpening
+        if options.sharpen > 0.0 {
+            adjust::sharpen(&mut small, options.sharpen);
+        }
+        // Averaging cells already yields in-between values, the extra source precision is dropped
+        let mut quantized = quantize(small, None, palette_size, options)?;
+        quantized.image = pixelate::upscale(&quantized.image, tiling, width, height);
+        quantized.indices = pixelate::upscale(&quantized.indices, tiling, width, height);
+        // Alpha gets the same cells so edges follow their shape
+        quantized.alpha = alpha.map(|alpha| pixelate::upscale(&pixelate::downsample(&alpha, tiling, false), tiling, width, height));
+        quantized
+    } else {
+        if options.sharpen > 0.0 {
+            adjust::sharpen(&mut rgb_img, options.sharpen);
+        }
+        let mut quantized = quantize(rgb_img, detail, palette_size, options)?;
+        quantized.alpha = alpha;
+        quantized
+    };
+    
+    // Before resizing, so the character cells stay whole numbers of mosaic cells
+    if options.teletext_mosaic {
+        let sixel = tiling.map_or((1, 1), |tiling| (tiling.width, tiling.height));
+        let cell = (sixel.0 * effects::TELETEXT_SIXELS.0, sixel.1 * effects::TELETEXT_SIXELS.1);
+        effects::attribute_clash(&mut quantized, matching_metric(options), cell);
+    }
+    
+    // Resize last so interpolation can't introduce colors outside the palette
+    let target = match (options.target_width, options.target_height) {
+        (Some(w), Some(h)) => Some((w, h)),
+        (Some(w), None) => Some((w, scale_dimension(height, w, width))),
+        (None, Some(h)) => Some((scale_dimension(width, h, height), h)),
+        (None, None) => None,
+    };
+    if let Some((w, h)) = target {
+        quantized.image = image::imageops::resize(&quantized.image, w, h, image::imageops::FilterType::Nearest);
+        quantized.indices = image::imageops::resize(&quantized.indices, w, h, image::imageops::FilterType::Nearest);
+        quantized.alpha = quantized.alpha.map(|alpha| image::imageops::resize(&alpha, w, h, image::imageops::FilterType::Nearest));
+    }
+    
+    // Before recoloring, so the darkened pixels are matched to the colors they were quantized with
+    if options.vignette_strength > 0.0 {
+        effects::vignette(&mut quantized, options.vignette_strength, options.linear_rgb, matching_metric(options));
+    }
+    
+    // On the final pixels, so the cells line up with the output whatever it was resized to
+    if options.attribute_clash {
+        effects::attribute_clash(&mut quantized, matching_metric(options), effects::ATTRIBUTE_CELL);
+    }
+    if options.c64_multicolor {
+        effects::c64_multicolor(&mut quantized, matching_metric(options));
+    }
+    
+    if let Some(recolor) = &options.recolor {
+        apply_recolor(&mut quantized, recolor);
+    }
+    
+    // After recoloring so the outline keeps exactly the requested color
+    if options.outline {
+        effects::outline(&mut quantized, options.outline_color, options.outline_thickness)?;
+    }
+    
+    if options.scanlines {
+        effects::scanlines(&mut quantized, options.scanline_spacing, options.scanline_intensity, options.linear_rgb)?;
+    }
+    
+    // Last, it describes the display rather than the image
+    if options.gamma != 1.0 {
+        effects::output_gamma(&mut quantized, options.gamma);
+    }
+    
+    options.report(1.0);
+    options.check_progress()?;
+    Ok(quantized)
+}
+
+// Swap output colors by exact match. Every pixel comes from the palette, so rewriting the palette
+// entries and the pixels together keeps the indices valid for indexed output.
+fn apply_recolor(quantized: &mut Quantized, recolor: &HashMap<Rgb<u8>, Rgb<u8>>) {
+    for color in quantized.palette.iter_mut().chain(quantized.image.pixels_mut()) {
+        if let Some(&replacement) = recolor.get(color) {
+            *color = replacement;
+        }
+    }
+}
+
+// Scale `size` by `numerator / denominator`, rounding and never going below 1
+fn scale_dimension(size: u32, numerator: u32, denominator: u32) -> u32 {
+    ((size as u64 * numerator as u64 + denominator as u64 / 2) / denominator.max(1) as u64).max(1) as u32
+}
+
+// Largest palette whose positions still fit in an `IndexImage`
+const MAX_PALETTE_COLORS: usize = 1 << 16;
+
+// Default `max_dimension` and `max_pixels`, far beyond any avatar but short of exhausting memory
+const DEFAULT_MAX_DIMENSION: u32 = 32_768;
+const DEFAULT_MAX_PIXELS: u64 = 100_000_000;
+
+// `palette_size` value that has the size estimated from the image
+const AUTO_PALETTE_SIZE: usize = 0;
+
+// Per-pixel positions into a palette
+type IndexImage = ImageBuffer<Luma<u16>, Vec<u16>>;
+
+// Per-pixel opacity, 0 transparent to 255 opaque
+type AlphaImage = ImageBuffer<Luma<u8>, Vec<u8>>;
+
+// An image reduced to a palette
+struct Quantized {
+    image: ImageBuffer<Rgb<u8>, Vec<u8>>,
+    // Palette position of every pixel in `image`
+    indices: IndexImage,
+    // The colors pixels were mapped to
+    palette: Vec<Rgb<u8>>,
+    // Source transparency, present when `preserve_alpha` is set
+    alpha: Option<AlphaImage>,
+}
+
+// Pick the palette selected by `options`. Ramp-style palettes convert `rgb_img` to grayscale for matching
+fn select_palette(rgb_img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, palette_size: usize, options: &Options) -> PyResult<Vec<Rgb<u8>>> {
+    // Use the requested preset, or create a limited color palette (8-bit has max 256 colors).
+    // A supplied palette is used as it is, `palette_size` only matters when one is generated.
+    let palette = match options.palette_preset {
+        // Comparing raw RGB against a few tinted shades misassigns saturated colors, so ramp
+        // presets quantize the luminance against evenly spaced greys and are recolored afterwards
+        Some(preset) if preset.luminance_ramp => {
+            *rgb_img = to_grayscale(rgb_img);
+            grey_ramp(preset.colors.len())
+        }
+        Some(preset) => match options.cga_palette {
+            Some(cga_palette) => palettes::CGA_MODE4[cga_palette].to_vec(),
+            None => preset.colors.to_vec(),
+        },
+        None => match &options.custom_palette {
+            Some(colors) => colors.clone(),
+            None => {
+                let palette_size = if palette_size == AUTO_PALETTE_SIZE { adaptive::estimate_size(rgb_img) } else { palette_size };
+                // `palette_size` isn't among the options, so this is the first place it can be checked
+                if options.preserve_extremes && palette_size < 2 {
+                    return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("preserve_extremes needs a palette_size of at least 2 to hold black and white, got {}", palette_size)));
+                }
+                let palette = match options.palette_mode {
+                    PaletteMode::Fixed if options.duotone.is_none() => generate_palette(palette_size.min(256)),
+                    PaletteMode::MedianCut => adaptive::median_cut(rgb_img, palette_size.min(256)),
+                    PaletteMode::KMeans => adaptive::kmeans(rgb_img, palette_size.min(256), options.kmeans_iterations, options.kmeans_tolerance, options.seed),
+                    PaletteMode::Octree => adaptive::octree(rgb_img, palette_size.min(256)),
+                    PaletteMode::Popularity => adaptive::popularity(rgb_img, palette_size.min(256), options.popularity_bits),
+                    // Always 64 colors, like the hardware mode
+                    PaletteMode::Ehb => adaptive::extra_half_brite(rgb_img),
+                    // `palette_size` evenly spaced greys, matched against the perceptual luminance
+                    // (and swapped for the duotone ramp by `output_palette`)
+                    PaletteMode::Fixed | PaletteMode::Grayscale => {
+                        *rgb_img = to_grayscale(rgb_img);
+                        grey_ramp(palette_size.min(256))
+                    }
+                };
+                if options.preserve_extremes { with_extremes(palette, rgb_img, palette_size.min(256)) } else { palette }
+            }
+        },
+    };
     
-    // Convert to RGB
-    let rgb_img = img.to_rgb8();
-    let (width, height) = rgb_img.dimensions();
+    // An empty palette would otherwise quietly turn every pixel black
+    if palette.is_empty() {
+        return Err(PyErr::new::<errors::InvalidPaletteError, _>("palette must contain at least one color"));
+    }
     
-    // Create a limited color palette (8-bit has max 256 colors)
-    let actual_palette_size = palette_size.min(256);
-    let mut palette = generate_palette(actual_palette_size);
+    Ok(palette)
+}
+
+// Add pure black and white to a generated palette of at most `size` colors. Once it's full they take
+// the place of the entries the fewest pixels are nearest to, everything else keeps its position.
+fn with_extremes(mut palette: Vec<Rgb<u8>>, img: &ImageBuffer<Rgb<u8>, Vec<u8>>, size: usize) -> Vec<Rgb<u8>> {
+    let extremes = [Rgb([0, 0, 0]), Rgb([255, 255, 255])];
+    let missing: Vec<Rgb<u8>> = extremes.into_iter().filter(|color| !palette.contains(color)).collect();
+    let overflow = (palette.len() + missing.len()).saturating_sub(size).min(missing.len());
+    let (replacing, adding) = missing.split_at(overflow);
     
-    // Create output image
-    let mut output_img = ImageBuffer::new(width, height);
+    if !replacing.is_empty() {
+        let mut counts = vec![0u64; palette.len()];
+        for (color, count) in adaptive::histogram(img) {
+            counts[find_nearest_index(&color, &palette)] += count as u64;
+        }
+        // Least used first, later entries first among equals
+        let mut slots: Vec<usize> = (0..palette.len()).filter(|&i| !extremes.contains(&palette[i])).collect();
+        slots.sort_by_key(|&i| (counts[i], std::cmp::Reverse(i)));
+        for (&slot, &color) in slots.iter().zip(replacing) {
+            palette[slot] = color;
+        }
+    }
+    palette.extend_from_slice(adding);
+    palette
+}
+
+// Reduce an RGB image to the palette selected by `options`. `detail` is the sub-level precision of
+// a high bit depth source, which error diffusion takes into account.
+fn quantize(mut rgb_img: ImageBuffer<Rgb<u8>, Vec<u8>>, mut detail: Option<Vec<[f32; 3]>>, palette_size: usize, options: &Options) -> PyResult<Quantized> {
+    let matcher = {
+        let _stage = options.stage("palette");
+        build_matcher(&mut rgb_img, palette_size, options)?
+    };
+    options.report(PALETTE_PROGRESS);
+    options.check_progress()?;
     
-    // Apply 8-bit conversion
-    for y in 0..height {
-        for x in 0..width {
-            let pixel = rgb_img.get_pixel(x, y);
-            
-            // Either apply dithering or direct color mapping
-            let new_pixel = if dithering {
-                apply_dithering(&rgb_img, x, y, &palette)
-            } else {
-                find_nearest_color(pixel, &palette)
-            };
-            
-            output_img.put_pixel(x, y, new_pixel);
+    // Palettes matched on luminance turned the image grey, the detail has to follow
+    if matches_luminance(options) {
+        if let Some(detail) = &mut detail {
+            for offset in detail.iter_mut() {
+                let grey = 0.299 * offset[0] + 0.587 * offset[1] + 0.114 * offset[2];
+                *offset = [grey; 3];
+            }
         }
     }
     
-    // Convert output image to bytes
-    let mut output_bytes = Cursor::new(Vec::new());
-    output_img.write_to(&mut output_bytes, image::ImageOutputFormat::Png)
-        .map_err(|e| PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Failed to encode image: {}", e)))?;
+    // After the palette is built, so the noise only changes which colors get picked
+    if options.predither_noise > 0.0 {
+        add_predither_noise(&mut rgb_img, 0, options);
+    }
     
-    // Return bytes to Python
-    Python::with_gil(|py| {
-        Ok(PyBytes::new(py, &output_bytes.into_inner()).into())
-    })
+    // Apply 8-bit conversion, either with error diffusion or direct color mapping
+    let indices = {
+        let _stage = options.stage("quantize");
+        let diffuse = |kernel: &[(i32, i32, f32)]| apply_dithering(&rgb_img, detail.as_deref(), &matcher, kernel, options);
+        match options.dither_algorithm {
+            DitherAlgorithm::FloydSteinberg => diffuse(&FLOYD_STEINBERG),
+            DitherAlgorithm::Atkinson => diffuse(&ATKINSON),
+            DitherAlgorithm::Jarvis => diffuse(&JARVIS_JUDICE_NINKE),
+            DitherAlgorithm::Stucki => diffuse(&STUCKI),
+            DitherAlgorithm::Burkes => diffuse(&BURKES),
+            DitherAlgorithm::Sierra => diffuse(&SIERRA),
+            DitherAlgorithm::SierraLite => diffuse(&SIERRA_LITE),
+            DitherAlgorithm::Custom => diffuse(options.dither_kernel.as_deref().unwrap_or_default()),
+            DitherAlgorithm::Ordered => apply_ordered_dithering(&rgb_img, &matcher, options.bayer_size, ordered_spread(&matcher, options)),
+            DitherAlgorithm::Riemersma => apply_riemersma(&rgb_img, detail.as_deref(), &matcher, options),
+            DitherAlgorithm::BlueNoise => apply_blue_noise_dithering(&rgb_img, &matcher, ordered_spread(&matcher, options)),
+            DitherAlgorithm::Halftone => apply_halftone(&rgb_img, &matcher, options.halftone_spacing, options.halftone_shape),
+            DitherAlgorithm::None => map_pixels(&rgb_img, |_, _, pixel| matcher.nearest_index(pixel) as u16),
+        }
+    };
+    options.report(QUANTIZE_PROGRESS);
+    options.check_progress()?;
+    
+    let used_palette = output_palette(&matcher, options);
+    let output_img = ImageBuffer::from_fn(indices.width(), indices.height(), |x, y| used_palette[indices.get_pixel(x, y)[0] as usize]);
+    Ok(Quantized { image: output_img, indices, palette: used_palette, alpha: None })
+}
+
+// Whether the palette selected by `options` is matched on luminance, in which case `select_palette`
+// turns the image grey and the matched greys are swapped for the real colors afterwards
+fn matches_luminance(options: &Options) -> bool {
+    options.palette_preset.is_some_and(|preset| preset.luminance_ramp) || options.palette_mode == PaletteMode::Grayscale || options.duotone.is_some()
+}
+
+// The colors that actually appear in the output. Ramp presets and duotone line up one-to-one with
+// the greys they were matched against, so recoloring is just swapping the palette
+fn output_palette(matcher: &ColorMatcher, options: &Options) -> Vec<Rgb<u8>> {
+    if let Some((shadow, highlight)) = options.duotone {
+        return matcher.palette().iter()
+            .map(|grey| {
+                let t = grey[0] as u32;
+                Rgb([0, 1, 2].map(|c| ((shadow[c] as u32 * (255 - t) + highlight[c] as u32 * t + 127) / 255) as u8))
+            })
+            .collect();
+    }
+    match options.palette_preset.filter(|preset| preset.luminance_ramp) {
+        Some(preset) => preset.colors.to_vec(),
+        None => matcher.palette().to_vec(),
+    }
+}
+
+// Palette size from which the k-d tree is built even without `kd_tree`. It finds the same colors
+// as a linear scan, which is quicker for smaller palettes.
+const KD_TREE_MIN_COLORS: usize = 128;
+
+// Set up nearest-color matching for the palette or shortcut selected by `options`
+fn build_matcher(rgb_img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, palette_size: usize, options: &Options) -> PyResult<ColorMatcher> {
+    let matcher = match (options.posterize_levels, options.bit_layout, options.threshold) {
+        // Posterizing, bit layouts and thresholding pick colors directly, there is no palette to build or search
+        (Some(levels), _, _) => ColorMatcher::posterize(levels as usize),
+        (None, Some(layout), _) => ColorMatcher::bit_layout(layout),
+        (None, None, Some(threshold)) => {
+            let background = options.background.unwrap_or(Rgb([0, 0, 0]));
+            let foreground = options.foreground.unwrap_or(Rgb([255, 255, 255]));
+            ColorMatcher::threshold(background, foreground, threshold)
+        }
+        (None, None, None) => {
+            let palette = select_palette(rgb_img, palette_size, options)?;
+            let mut matcher = ColorMatcher::new(palette, matching_metric(options));
+            if options.kd_tree || matcher.palette().len() >= KD_TREE_MIN_COLORS {
+                matcher = matcher.with_kd_tree();
+            }
+            if options.lookup_cache {
+                matcher = matcher.with_cache();
+            }
+            
+            matcher
+        }
+    };
+    Ok(matcher)
+}
+
+// The metric colors are matched with, plain RGB distances are measured in linear light with `linear_rgb`
+fn matching_metric(options: &Options) -> DistanceMetric {
+    match options.distance_metric {
+        DistanceMetric::Rgb if options.linear_rgb => DistanceMetric::LinearRgb,
+        metric => metric,
+    }
 }
 
+// Progress reported once the palette is ready and once every pixel has been matched, error diffusion
+// reports the rows in between
+const PALETTE_PROGRESS: f32 = 0.1;
+const QUANTIZE_PROGRESS: f32 = 0.9;
+
+// How many progress reports are spread over the rows of error diffusion
+const PROGRESS_STEPS: u32 = 20;
+
 // Generate a fixed palette of colors for 8-bit aesthetic
 fn generate_palette(size: usize) -> Vec<Rgb<u8>> {
     let mut palette = Vec::with_capacity(size);
@@ -64,42 +1685,103 @@ fn generate_palette(size: usize) -> Vec<Rgb<u8>> {
     palette.push(Rgb([255, 0, 255]));   // Magenta
     palette.push(Rgb([0, 255, 255]));   // Cyan
     
-    // Fill remaining slots with evenly distributed RGB values
+    palette.truncate(size);
+    
+    // Fill remaining slots with an evenly spaced RGB grid. Pick the number of levels per channel so
+    // the grid fits in `size`, giving green an extra level first since the eye is most sensitive to it
     if size > 8 {
-        let steps = ((size - 8) as f32).cbrt().ceil() as u8;
-        let step_size = 255 / steps;
-        
-        for r in (0..=255).step_by(step_size as usize) {
-            for g in (0..=255).step_by(step_size as usize) {
-                for b in (0..=255).step_by(step_size as usize) {
-                    if palette.len() >= size {
-                        break;
-                    }
-                    // Skip if too similar to existing colors
-                    let new_color = Rgb([r as u8, g as u8, b as u8]);
-                    if !palette.contains(&new_color) {
+        let cube = (size as f32).cbrt().floor().max(2.0) as usize;
+        let mut levels = [cube, cube, cube];
+        for channel in [1, 0, 2] {
+            levels[channel] += 1;
+            if levels.iter().product::<usize>() > size {
+                levels[channel] -= 1;
+                break;
+            }
+        }
+        
+        // Levels always include 0 and 255, so the basic colors above are part of the grid
+        let level = |i: usize, count: usize| (i * 255 / (count - 1)) as u8;
+        for r in 0..levels[0] {
+            for g in 0..levels[1] {
+                for b in 0..levels[2] {
+                    let new_color = Rgb([level(r, levels[0]), level(g, levels[1]), level(b, levels[2])]);
+                    if is_distinct(&palette, &new_color) {
                         palette.push(new_color);
                     }
                 }
-                if palette.len() >= size {
-                    break;
-                }
             }
-            if palette.len() >= size {
-                break;
+        }
+        
+        // Whatever the grid couldn't fill goes to evenly spaced greys
+        let remaining = size.saturating_sub(palette.len());
+        for i in 1..=remaining {
+            let grey = (i * 255 / (remaining + 1)) as u8;
+            let new_color = Rgb([grey, grey, grey]);
+            if is_distinct(&palette, &new_color) {
+                palette.push(new_color);
+            }
+        }
+        
+        // Greys too close to the grid are dropped, top up from ever finer grids until the palette is full
+        let mut count = levels.iter().max().copied().unwrap_or(2);
+        while palette.len() < size && count < 256 {
+            count += 1;
+            for r in 0..count {
+                for g in 0..count {
+                    for b in 0..count {
+                        let new_color = Rgb([level(r, count), level(g, count), level(b, count)]);
+                        if palette.len() < size && is_distinct(&palette, &new_color) {
+                            palette.push(new_color);
+                        }
+                    }
+                }
             }
         }
     }
     
+    // Dark to light, so neighbouring indices hold similar brightness for cycling and indexed output
+    palette.sort_by(|a, b| luminance(a).total_cmp(&luminance(b)));
+    
     palette
 }
 
-// Find the nearest color in the palette
-fn find_nearest_color(pixel: &Rgb<u8>, palette: &[Rgb<u8>]) -> Rgb<u8> {
-    palette.iter()
-        .min_by_key(|&&p| color_distance(pixel, &p))
-        .unwrap_or(&Rgb([0, 0, 0]))
-        .clone()
+// Colors closer than this (squared RGB distance) to one already in a generated palette are dropped
+const MIN_PALETTE_DISTANCE: u32 = 24 * 24;
+
+// Whether `color` is far enough from every color in `palette` to be worth a slot
+fn is_distinct(palette: &[Rgb<u8>], color: &Rgb<u8>) -> bool {
+    palette.iter().all(|existing| color_distance(existing, color) >= MIN_PALETTE_DISTANCE)
+}
+
+// Perceptual luminance of a color (ITU-R BT.601 weights)
+fn luminance(pixel: &Rgb<u8>) -> f32 {
+    0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32
+}
+
+// Replace every pixel with a grey of the same luminance
+fn to_grayscale(img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    ImageBuffer::from_fn(img.width(), img.height(), |x, y| {
+        let grey = luminance(img.get_pixel(x, y)).round() as u8;
+        Rgb([grey, grey, grey])
+    })
+}
+
+// Evenly spaced greys from black to white
+fn grey_ramp(levels: usize) -> Vec<Rgb<u8>> {
+    (0..levels)
+        .map(|i| {
+            let grey = (i * 255 / (levels - 1).max(1)) as u8;
+            Rgb([grey, grey, grey])
+        })
+        .collect()
+}
+
+// Find the position of the nearest color in the palette
+fn find_nearest_index(pixel: &Rgb<u8>, palette: &[Rgb<u8>]) -> usize {
+    (0..palette.len())
+        .min_by_key(|&i| color_distance(pixel, &palette[i]))
+        .unwrap_or(0)
 }
 
 // Calculate Euclidean distance between colors
@@ -118,18 +1800,807 @@ fn color_distance(c1: &Rgb<u8>, c2: &Rgb<u8>) -> u32 {
     (dr*dr + dg*dg + db*db) as u32
 }
 
-// Apply Floyd-Steinberg dithering
-fn apply_dithering(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, x: u32, y: u32, palette: &[Rgb<u8>]) -> Rgb<u8> {
-    let pixel = img.get_pixel(x, y);
-    let nearest = find_nearest_color(pixel, palette);
+// Floyd-Steinberg error diffusion weights as (dx, dy, weight)
+const FLOYD_STEINBERG: [(i32, i32, f32); 4] = [
+    (1, 0, 7.0 / 16.0),
+    (-1, 1, 3.0 / 16.0),
+    (0, 1, 5.0 / 16.0),
+    (1, 1, 1.0 / 16.0),
+];
+
+// Atkinson diffuses only 6/8 of the error, the remaining 2/8 is discarded
+// which is what gives it brighter highlights than Floyd-Steinberg
+const ATKINSON: [(i32, i32, f32); 6] = [
+    (1, 0, 1.0 / 8.0),
+    (2, 0, 1.0 / 8.0),
+    (-1, 1, 1.0 / 8.0),
+    (0, 1, 1.0 / 8.0),
+    (1, 1, 1.0 / 8.0),
+    (0, 2, 1.0 / 8.0),
+];
+
+// Jarvis-Judice-Ninke spreads the error over 12 neighbours across three rows (divisor 48)
+const JARVIS_JUDICE_NINKE: [(i32, i32, f32); 12] = [
+    (1, 0, 7.0 / 48.0),
+    (2, 0, 5.0 / 48.0),
+    (-2, 1, 3.0 / 48.0),
+    (-1, 1, 5.0 / 48.0),
+    (0, 1, 7.0 / 48.0),
+    (1, 1, 5.0 / 48.0),
+    (2, 1, 3.0 / 48.0),
+    (-2, 2, 1.0 / 48.0),
+    (-1, 2, 3.0 / 48.0),
+    (0, 2, 5.0 / 48.0),
+    (1, 2, 3.0 / 48.0),
+    (2, 2, 1.0 / 48.0),
+];
+
+// Stucki uses the same footprint as Jarvis-Judice-Ninke with sharper weights (divisor 42)
+const STUCKI: [(i32, i32, f32); 12] = [
+    (1, 0, 8.0 / 42.0),
+    (2, 0, 4.0 / 42.0),
+    (-2, 1, 2.0 / 42.0),
+    (-1, 1, 4.0 / 42.0),
+    (0, 1, 8.0 / 42.0),
+    (1, 1, 4.0 / 42.0),
+    (2, 1, 2.0 / 42.0),
+    (-2, 2, 1.0 / 42.0),
+    (-1, 2, 2.0 / 42.0),
+    (0, 2, 4.0 / 42.0),
+    (1, 2, 2.0 / 42.0),
+    (2, 2, 1.0 / 42.0),
+];
+
+// Burkes is Stucki without the third row, which makes it faster (divisor 32)
+const BURKES: [(i32, i32, f32); 7] = [
+    (1, 0, 8.0 / 32.0),
+    (2, 0, 4.0 / 32.0),
+    (-2, 1, 2.0 / 32.0),
+    (-1, 1, 4.0 / 32.0),
+    (0, 1, 8.0 / 32.0),
+    (1, 1, 4.0 / 32.0),
+    (2, 1, 2.0 / 32.0),
+];
+
+// Sierra (Sierra-3) spreads the error over 10 neighbours across three rows (divisor 32)
+const SIERRA: [(i32, i32, f32); 10] = [
+    (1, 0, 5.0 / 32.0),
+    (2, 0, 3.0 / 32.0),
+    (-2, 1, 2.0 / 32.0),
+    (-1, 1, 4.0 / 32.0),
+    (0, 1, 5.0 / 32.0),
+    (1, 1, 4.0 / 32.0),
+    (2, 1, 2.0 / 32.0),
+    (-1, 2, 2.0 / 32.0),
+    (0, 2, 3.0 / 32.0),
+    (1, 2, 2.0 / 32.0),
+];
+
+// Sierra Lite only touches three neighbours (divisor 4), the cheapest kernel here
+const SIERRA_LITE: [(i32, i32, f32); 3] = [
+    (1, 0, 2.0 / 4.0),
+    (-1, 1, 1.0 / 4.0),
+    (0, 1, 1.0 / 4.0),
+];
+
+// Quantization errors accumulate, so error diffusion works on a float copy of the image.
+// With `linear_rgb` the copy holds linear light (scaled to 0-255) so errors add up physically.
+// `detail` holds per-pixel offsets (in 0-255 units) that restore precision `img` couldn't store.
+fn working_copy(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, detail: Option<&[[f32; 3]]>, options: &Options) -> Vec<[f32; 3]> {
+    match detail {
+        Some(detail) => img.pixels().zip(detail)
+            .map(|(p, offset)| {
+                let value = [0, 1, 2].map(|c| (p[c] as f32 + offset[c]).clamp(0.0, 255.0));
+                if options.linear_rgb { value.map(|c| color::srgb_fraction_to_linear(c / 255.0) * 255.0) } else { value }
+            })
+            .collect(),
+        None => img.pixels().map(|p| to_working(p, options)).collect(),
+    }
+}
+
+// A color as it is held in the working copy
+fn to_working(p: &Rgb<u8>, options: &Options) -> [f32; 3] {
+    if options.linear_rgb { color::rgb_to_linear(p) } else { [p[0] as f32, p[1] as f32, p[2] as f32] }
+}
+
+// The color to match for a (clamped) working copy value
+fn from_working(value: [f32; 3], options: &Options) -> Rgb<u8> {
+    if options.linear_rgb {
+        Rgb(value.map(|c| color::linear_to_srgb(c / 255.0)))
+    } else {
+        Rgb(value.map(|c| c.round() as u8))
+    }
+}
+
+// Apply error diffusion dithering, spreading each pixel's quantization error with the given kernel
+fn apply_dithering(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, detail: Option<&[[f32; 3]]>, matcher: &ColorMatcher, kernel: &[(i32, i32, f32)], options: &Options) -> IndexImage {
+    let (width, height) = img.dimensions();
+    let mut output_img = ImageBuffer::new(width, height);
+    let mut buffer = working_copy(img, detail, options);
+    // The source colors, kept apart from the accumulated error when it has to stay within a margin
+    // of them or highlights are matched without it
+    let source = (options.dither_clamp || options.dither_shadows.is_some()).then(|| buffer.clone());
+    let cell = options.dither_cell();
+    
+    let report_every = (height / PROGRESS_STEPS).max(1);
+    for y in 0..height {
+        if y % report_every == 0 {
+            options.report(PALETTE_PROGRESS + (QUANTIZE_PROGRESS - PALETTE_PROGRESS) * y as f32 / height as f32);
+        }
+        
+        // Serpentine scanning walks odd rows right-to-left with the kernel mirrored horizontally
+        let reversed = options.serpentine && y % 2 == 1;
+        let direction = if reversed { -1 } else { 1 };
+        
+        for i in 0..width {
+            let x = if reversed { width - 1 - i } else { i };
+            let idx = (y * width + x) as usize;
+            
+            // Highlights with `dither_shadows` ignore the error that reached them and pass none on
+            let dithered = in_shadow(img.get_pixel(x, y), options);
+            let value = match &source {
+                Some(source) if !dithered => source[idx],
+                _ => buffer[idx],
+            };
+            // Clamp so accumulated error can't wrap bright/dark regions around
+            let value = value.map(|c| c.clamp(0.0, 255.0));
+            let index = matcher.nearest_index(&from_working(value, options));
+            output_img.put_pixel(x, y, Luma([index as u16]));
+            let nearest = to_working(&matcher.palette()[index], options);
+            
+            let strength = if dithered { options.dither_strength } else { 0.0 };
+            let error = [
+                (value[0] - nearest[0]) * strength,
+                (value[1] - nearest[1]) * strength,
+                (value[2] - nearest[2]) * strength,
+            ];
+            
+            // Push the error onto neighbours that haven't been visited yet, skipping any outside the
+            // image or, with `dither_per_tile`, outside this pixel's cell
+            for &(dx, dy, weight) in kernel {
+                let nx = x as i32 + dx * direction;
+                let ny = y as i32 + dy;
+                if nx < 0 || nx >= width as i32 || ny >= height as i32 {
+                    continue;
+                }
+                if cell.is_some_and(|(cell_width, cell_height)| nx as u32 / cell_width != x / cell_width || ny as u32 / cell_height != y / cell_height) {
+                    continue;
+                }
+                let neighbour_idx = (ny as u32 * width + nx as u32) as usize;
+                let neighbour = &mut buffer[neighbour_idx];
+                for c in 0..3 {
+                    neighbour[c] += error[c] * weight;
+                }
+                if let Some(source) = source.as_ref().filter(|_| options.dither_clamp) {
+                    let margin = options.dither_clamp_margin;
+                    *neighbour = [0, 1, 2].map(|c| neighbour[c].clamp(source[neighbour_idx][c] - margin, source[neighbour_idx][c] + margin));
+                }
+            }
+        }
+    }
+    
+    output_img
+}
+
+// Whether error diffusion applies to a source pixel, see `dither_shadows`
+fn in_shadow(pixel: &Rgb<u8>, options: &Options) -> bool {
+    options.dither_shadows.is_none_or(|threshold| luminance(pixel) < threshold as f32)
+}
+
+// Number of past errors Riemersma dithering remembers, and how much more the newest one counts
+// than the oldest
+const RIEMERSMA_HISTORY: usize = 16;
+const RIEMERSMA_RATIO: f32 = 16.0;
+
+// Apply Riemersma dithering
+//
+// Pixels are visited along a Hilbert curve instead of row by row, and each one is corrected by the
+// errors of the last few pixels on the curve with exponentially decaying weights. Since the curve
+// keeps turning, there is no direction for errors to pile up along and the grain stays even.
+fn apply_riemersma(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, detail: Option<&[[f32; 3]]>, matcher: &ColorMatcher, options: &Options) -> IndexImage {
+    let (width, height) = img.dimensions();
+    let mut output_img = ImageBuffer::new(width, height);
+    let buffer = working_copy(img, detail, options);
+    
+    // Oldest weight first, the newest is RIEMERSMA_RATIO times larger and counts as a whole error
+    let weights: Vec<f32> = (0..RIEMERSMA_HISTORY)
+        .map(|i| RIEMERSMA_RATIO.powf(i as f32 / (RIEMERSMA_HISTORY - 1) as f32) / RIEMERSMA_RATIO)
+        .collect();
+    let mut history = VecDeque::from(vec![[0f32; 3]; RIEMERSMA_HISTORY]);
+    let cell = options.dither_cell();
+    
+    let path = hilbert::curve(width, height);
+    let report_every = (path.len() / PROGRESS_STEPS as usize).max(1);
+    for (step, &(x, y)) in path.iter().enumerate() {
+        if step % report_every == 0 {
+            options.report(PALETTE_PROGRESS + (QUANTIZE_PROGRESS - PALETTE_PROGRESS) * step as f32 / path.len() as f32);
+        }
+        
+        // With `dither_per_tile` the errors of other cells are forgotten whenever the curve enters a new one
+        if let Some((cell_width, cell_height)) = cell {
+            if step > 0 && (path[step - 1].0 / cell_width != x / cell_width || path[step - 1].1 / cell_height != y / cell_height) {
+                history.iter_mut().for_each(|error| *error = [0.0; 3]);
+            }
+        }
+        
+        let original = buffer[(y * width + x) as usize];
+        let dithered = in_shadow(img.get_pixel(x, y), options);
+        let mut value = original;
+        for (error, weight) in history.iter().zip(&weights).filter(|_| dithered) {
+            for c in 0..3 {
+                value[c] += error[c] * weight * options.dither_strength;
+            }
+        }
+        if options.dither_clamp {
+            value = [0, 1, 2].map(|c| value[c].clamp(original[c] - options.dither_clamp_margin, original[c] + options.dither_clamp_margin));
+        }
+        let value = value.map(|c| c.clamp(0.0, 255.0));
+        let index = matcher.nearest_index(&from_working(value, options));
+        output_img.put_pixel(x, y, Luma([index as u16]));
+        
+        // The error is taken against the pixel itself, not the corrected value, so it can't build up
+        let nearest = to_working(&matcher.palette()[index], options);
+        history.pop_front();
+        history.push_back(if dithered { [0, 1, 2].map(|c| original[c] - nearest[c]) } else { [0.0; 3] });
+    }
+    
+    output_img
+}
+
+// How far (in 0-255 channel units) the ordered dithering thresholds spread pixels: the given
+// `dither_spread`, or the typical step between palette colors so every gap is dithered across
+// exactly once whether the palette has 4 colors or 64
+fn ordered_spread(matcher: &ColorMatcher, options: &Options) -> f32 {
+    options.dither_spread.unwrap_or_else(|| matcher.mean_spacing())
+}
+
+// Build an n x n Bayer threshold matrix with values 0..n*n, n must be a power of two
+fn bayer_matrix(n: u32) -> Vec<u32> {
+    let mut matrix = vec![0];
+    let mut size = 1;
+    
+    // Each step tiles the previous matrix into quadrants scaled by 4 and offset by 0, 2, 3, 1
+    while size < n {
+        let next_size = size * 2;
+        let mut next = vec![0; (next_size * next_size) as usize];
+        for y in 0..size {
+            for x in 0..size {
+                let value = 4 * matrix[(y * size + x) as usize];
+                next[(y * next_size + x) as usize] = value;
+                next[(y * next_size + x + size) as usize] = value + 2;
+                next[((y + size) * next_size + x) as usize] = value + 3;
+                next[((y + size) * next_size + x + size) as usize] = value + 1;
+            }
+        }
+        matrix = next;
+        size = next_size;
+    }
+    
+    matrix
+}
+
+// Apply ordered (Bayer) dithering
+//
+// Every pixel is biased by a fixed threshold from the matrix before the nearest-color lookup.
+// There is no error propagation, so each pixel only depends on its own value and position.
+fn apply_ordered_dithering(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, matcher: &ColorMatcher, matrix_size: u32, spread: f32) -> IndexImage {
+    let matrix = bayer_matrix(matrix_size);
+    apply_threshold_matrix(img, matcher, matrix_size, spread, |x, y| matrix[(x * matrix_size + y) as usize] as f32)
+}
+
+// Apply blue-noise dithering, the same as ordered dithering but with the void-and-cluster matrix
+// whose thresholds have no pattern to them
+fn apply_blue_noise_dithering(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, matcher: &ColorMatcher, spread: f32) -> IndexImage {
+    apply_threshold_matrix(img, matcher, bluenoise::SIZE, spread, |x, y| bluenoise::BLUE_NOISE[(y * bluenoise::SIZE + x) as usize] as f32)
+}
+
+// Bias every pixel by the threshold `threshold(x, y)` (0..size*size) of a tiled `size` x `size`
+// matrix, scaled to `spread` channel units, before the nearest-color lookup
+fn apply_threshold_matrix(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, matcher: &ColorMatcher, size: u32, spread: f32, threshold: impl Fn(u32, u32) -> f32 + Sync) -> IndexImage {
+    let cells = (size * size) as f32;
+    
+    map_pixels(img, |x, y, pixel| {
+        let bias = (threshold(x % size, y % size) / cells - 0.5) * spread;
+        let biased = Rgb(pixel.0.map(|c| (c as f32 + bias).clamp(0.0, 255.0).round() as u8));
+        matcher.nearest_index(&biased) as u16
+    })
+}
+
+// Add triangular (TPDF) noise of up to `options.predither_noise` to every channel of `img`, whose first
+// row is row `top` of the whole image. The noise is a hash of the seed and pixel position, so rows can
+// be done in any order and strips get the same noise as the whole image would.
+fn add_predither_noise(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, top: u32, options: &Options) {
+    let width = img.width() as usize;
+    if width == 0 {
+        return;
+    }
+    let amplitude = options.predither_noise;
+    // Images matched on luminance are grey, the same noise on every channel keeps them that way
+    let grey = matches_luminance(options);
+    
+    img.par_chunks_mut(width * 3).enumerate().for_each(|(y, row)| {
+        let y = top as u64 + y as u64;
+        for (x, pixel) in row.chunks_mut(3).enumerate() {
+            for (c, value) in pixel.iter_mut().enumerate() {
+                let channel = if grey { 0 } else { c as u64 };
+                let hash = mix(options.seed ^ mix((y << 34) ^ ((x as u64) << 2) ^ channel));
+                // The difference of two uniform values in 0.0-1.0 has a triangular distribution over -1.0-1.0
+                let noise = ((hash >> 32) as f32 - (hash & 0xFFFF_FFFF) as f32) / u32::MAX as f32;
+                *value = (*value as f32 + noise * amplitude).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    });
+}
+
+// SplitMix64 finalizer, turns neighbouring inputs into unrelated 64-bit values
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// Thresholds for one halftone cell in 0.0-1.0, growing outwards from the center so that any level
+// covers a single solid dot. Round dots grow by straight-line distance, square dots by the summed offsets.
+// Thresholds are ranks rather than distances so the dot area grows evenly with the level.
+fn spot_matrix(spacing: u32, shape: HalftoneShape) -> Vec<f32> {
+    let center = (spacing - 1) as f32 / 2.0;
+    let distance = |i: u32| {
+        let dx = (i % spacing) as f32 - center;
+        let dy = (i / spacing) as f32 - center;
+        match shape {
+            HalftoneShape::Round => dx * dx + dy * dy,
+            // Square dots are turned 45 degrees like a print screen, so the corners of neighbouring
+            // dots meet in a checkerboard at half coverage
+            HalftoneShape::Square => dx.abs() + dy.abs(),
+        }
+    };
+    
+    let cells = spacing * spacing;
+    let mut order: Vec<u32> = (0..cells).collect();
+    order.sort_by(|&a, &b| distance(a).total_cmp(&distance(b)));
+    let mut matrix = vec![0.0; cells as usize];
+    for (rank, &i) in order.iter().enumerate() {
+        matrix[i as usize] = (rank as f32 + 0.5) / cells as f32;
+    }
+    matrix
+}
+
+// Apply halftone dithering
+//
+// Each `spacing` x `spacing` cell is drawn as a dot whose size follows the cell's average color, dark
+// cells getting large dots. The spot thresholds push pixels across the whole channel range around that
+// average, so with two colors the dot is solid ink on paper no matter its size.
+fn apply_halftone(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, matcher: &ColorMatcher, spacing: u32, shape: HalftoneShape) -> IndexImage {
+    let matrix = spot_matrix(spacing, shape);
+    let averages = pixelate::downsample(img, Tiling::square(spacing), false);
+    
+    map_pixels(img, |x, y, _| {
+        let average = averages.get_pixel(x / spacing, y / spacing);
+        let bias = (matrix[((y % spacing) * spacing + x % spacing) as usize] - 0.5) * 255.0;
+        let biased = Rgb(average.0.map(|c| (c as f32 + bias).clamp(0.0, 255.0).round() as u8));
+        matcher.nearest_index(&biased) as u16
+    })
+}
+
+// Map every pixel independently to a palette index, spreading rows across threads
+//
+// Only usable when a pixel's output doesn't depend on any other pixel, error diffusion
+// has to stay serial. Output is identical to a serial loop whatever order rows run in.
+fn map_pixels<F>(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, f: F) -> IndexImage
+where
+    F: Fn(u32, u32, &Rgb<u8>) -> u16 + Sync,
+{
+    let (width, height) = img.dimensions();
+    let mut output_img: IndexImage = ImageBuffer::new(width, height);
+    if width == 0 {
+        return output_img;
+    }
+    
+    output_img.par_chunks_mut(width as usize)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, out) in row.iter_mut().enumerate() {
+                *out = f(x as u32, y as u32, img.get_pixel(x as u32, y as u32));
+            }
+        });
     
-    // For simplicity, we're not implementing the full dithering algorithm here
-    // A real implementation would propagate quantization errors to neighboring pixels
-    nearest
+    output_img
 }
 
 #[pymodule]
-fn rust_8bit(_py: Python, m: &PyModule) -> PyResult<()> {
+fn rust_8bit(py: Python, m: &PyModule) -> PyResult<()> {
+    errors::register(py, m)?;
     m.add_function(wrap_pyfunction!(convert_to_8bit, m)?)?;
+    m.add_function(wrap_pyfunction!(convert_with_metadata, m)?)?;
+    m.add_function(wrap_pyfunction!(convert_with_indices, m)?)?;
+    m.add_function(wrap_pyfunction!(convert_array_to_8bit, m)?)?;
+    m.add_function(wrap_pyfunction!(convert_batch_to_8bit, m)?)?;
+    m.add_function(wrap_pyfunction!(convert_file_to_8bit, m)?)?;
+    m.add_function(wrap_pyfunction!(convert_to_cycling_gif, m)?)?;
+    m.add_function(wrap_pyfunction!(extract_dominant_colors, m)?)?;
+    m.add_function(wrap_pyfunction!(convert_to_ascii, m)?)?;
+    m.add_function(wrap_pyfunction!(render_palette_swatch, m)?)?;
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // Options as `convert`'s keyword defaults give them, with error diffusion picked by `algorithm`
+    fn options(algorithm: DitherAlgorithm) -> Options {
+        Options { dither_algorithm: algorithm, ..Options::from_kwargs(false, None).unwrap() }
+    }
+    
+    // Horizontal grey ramp from black to white
+    fn gradient(width: u32, height: u32) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        ImageBuffer::from_fn(width, height, |x, _| {
+            let level = (x * 255 / (width - 1).max(1)) as u8;
+            Rgb([level, level, level])
+        })
+    }
+    
+    // Image of unrelated random colors, the same for the same `seed`
+    fn noise(width: u32, height: u32, seed: u64) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        ImageBuffer::from_fn(width, height, |x, y| {
+            let bits = mix(seed ^ ((y as u64) << 32 | x as u64)).to_le_bytes();
+            Rgb([bits[0], bits[1], bits[2]])
+        })
+    }
+    
+    // `img` encoded as a PNG file
+    fn png(img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Vec<u8> {
+        use image::ImageEncoder;
+        let mut bytes = Vec::new();
+        image::codecs::png::PngEncoder::new(&mut bytes).write_image(img.as_raw(), img.width(), img.height(), image::ColorType::Rgb8).unwrap();
+        bytes
+    }
+    
+    fn convert(img: ImageBuffer<Rgb<u8>, Vec<u8>>, palette_size: usize, options: &Options) -> Quantized {
+        convert_image(SourceImage { rgb: img, alpha: None, detail: None }, palette_size, options).ok().unwrap()
+    }
+    
+    fn distinct_colors(img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> HashSet<[u8; 3]> {
+        img.pixels().map(|pixel| pixel.0).collect()
+    }
+    
+    // Number of horizontally adjacent pixel pairs with different palette positions
+    fn transitions(indices: &IndexImage) -> usize {
+        let (width, height) = indices.dimensions();
+        (0..height)
+            .flat_map(|y| (1..width).map(move |x| (x, y)))
+            .filter(|&(x, y)| indices.get_pixel(x, y) != indices.get_pixel(x - 1, y))
+            .count()
+    }
+    
+    #[test]
+    fn floyd_steinberg_interleaves_colors_on_a_gradient() {
+        let plain = convert(gradient(64, 16), 4, &options(DitherAlgorithm::None));
+        let dithered = convert(gradient(64, 16), 4, &options(DitherAlgorithm::FloydSteinberg));
+        // Without dithering every row is a few solid bands, error diffusion mixes neighbouring colors
+        assert!(transitions(&plain.indices) <= 16 * 3);
+        assert!(transitions(&dithered.indices) > 4 * transitions(&plain.indices));
+    }
+    
+    #[test]
+    fn error_diffusion_kernels_differ() {
+        let img = ImageBuffer::from_fn(16, 16, |x, y| Rgb([(x * 17) as u8, (y * 17) as u8, 96]));
+        let kernels = [
+            DitherAlgorithm::FloydSteinberg,
+            DitherAlgorithm::Atkinson,
+            DitherAlgorithm::Jarvis,
+            DitherAlgorithm::Stucki,
+            DitherAlgorithm::Burkes,
+            DitherAlgorithm::Sierra,
+            DitherAlgorithm::SierraLite,
+        ];
+        let plain = convert(img.clone(), 8, &options(DitherAlgorithm::None)).indices;
+        let outputs: Vec<IndexImage> = kernels.iter().map(|&kernel| convert(img.clone(), 8, &options(kernel)).indices).collect();
+        for (i, output) in outputs.iter().enumerate() {
+            assert!(*output != plain, "kernel {} matches no dithering", i);
+            for (j, other) in outputs.iter().enumerate().skip(i + 1) {
+                assert!(output != other, "kernels {} and {} give the same output", i, j);
+            }
+        }
+    }
+    
+    #[test]
+    fn serpentine_reverses_odd_rows() {
+        // Vertical ramp, every row is a single flat grey between the two palette colors
+        let img = ImageBuffer::from_fn(32, 16, |_, y| Rgb([(64 + y * 8) as u8; 3]));
+        let raster = Options { custom_palette: Some(vec![Rgb([0, 0, 0]), Rgb([255, 255, 255])]), ..options(DitherAlgorithm::FloydSteinberg) };
+        let serpentine = Options { serpentine: true, custom_palette: raster.custom_palette.clone(), ..options(DitherAlgorithm::FloydSteinberg) };
+        let raster = convert(img.clone(), 2, &raster).indices;
+        let serpentine = convert(img, 2, &serpentine).indices;
+        let row = |indices: &IndexImage, y: u32| (0..32).map(|x| indices.get_pixel(x, y)[0]).collect::<Vec<_>>();
+        // The first row is walked left-to-right either way, the odd rows after it are not
+        assert_eq!(row(&raster, 0), row(&serpentine, 0));
+        assert!((1..16).step_by(2).any(|y| row(&raster, y) != row(&serpentine, y)));
+    }
+    
+    #[test]
+    fn generated_palettes_have_the_requested_size() {
+        for size in [2, 9, 16, 64, 256] {
+            assert_eq!(generate_palette(size).len(), size);
+        }
+    }
+    
+    #[test]
+    fn empty_palette_is_an_error() {
+        let options = Options { custom_palette: Some(Vec::new()), ..options(DitherAlgorithm::FloydSteinberg) };
+        let error = convert_image(SourceImage { rgb: gradient(8, 8), alpha: None, detail: None }, 16, &options).err();
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| assert!(error.is_some_and(|e| e.is_instance_of::<errors::InvalidPaletteError>(py))));
+    }
+    
+    #[test]
+    fn parallel_mapping_matches_a_serial_loop() {
+        // Odd sizes so rows don't split evenly across threads
+        let img = noise(97, 61, 7);
+        let matcher = ColorMatcher::new(generate_palette(32), DistanceMetric::Rgb);
+        let parallel = map_pixels(&img, |_, _, pixel| matcher.nearest_index(pixel) as u16);
+        let serial: IndexImage = ImageBuffer::from_fn(97, 61, |x, y| Luma([matcher.nearest_index(img.get_pixel(x, y)) as u16]));
+        assert!(parallel == serial);
+        assert!(convert(img, 32, &options(DitherAlgorithm::None)).indices == serial);
+    }
+    
+    #[test]
+    fn sierra_and_sierra_lite_differ() {
+        for kernel in [&SIERRA[..], &SIERRA_LITE[..]] {
+            assert!((kernel.iter().map(|&(_, _, weight)| weight).sum::<f32>() - 1.0).abs() < 1e-6);
+        }
+        let two_tone = |algorithm| Options { custom_palette: Some(vec![Rgb([0, 0, 0]), Rgb([255, 255, 255])]), ..options(algorithm) };
+        let sierra = convert(gradient(32, 32), 2, &two_tone(DitherAlgorithm::Sierra)).indices;
+        let sierra_lite = convert(gradient(32, 32), 2, &two_tone(DitherAlgorithm::SierraLite)).indices;
+        assert!(sierra != sierra_lite);
+    }
+    
+    #[test]
+    fn generated_palettes_are_distinct_and_sorted() {
+        for size in [2, 9, 16, 64, 256] {
+            let palette = generate_palette(size);
+            for (i, color) in palette.iter().enumerate() {
+                assert!(is_distinct(&palette[..i], color), "size {}: {:?} repeats an earlier color", size, color.0);
+            }
+            assert!(palette.windows(2).all(|pair| luminance(&pair[0]) <= luminance(&pair[1])));
+        }
+    }
+    
+    #[test]
+    fn extract_palette_decodes_like_convert() {
+        let two_tone = ImageBuffer::from_fn(8, 8, |x, _| if x < 6 { Rgb([200, 30, 30]) } else { Rgb([20, 20, 120]) });
+        // Wider than DEFAULT_MAX_DIMENSION, which has to be refused before decoding
+        let too_wide = ImageBuffer::from_pixel(DEFAULT_MAX_DIMENSION + 1, 1, Rgb([0, 0, 0]));
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            for method in ["median_cut", "kmeans", "octree", "popularity"] {
+                let colors = extract_dominant_colors(py, &png(&two_tone), 2, method, true).ok().unwrap();
+                assert_eq!(colors, vec![(200, 30, 30), (20, 20, 120)], "{}", method);
+            }
+            let error = extract_dominant_colors(py, &png(&too_wide), 2, "median_cut", true).err();
+            assert!(error.is_some_and(|e| e.is_instance_of::<errors::ImageTooLargeError>(py)));
+        });
+    }
+    
+    #[test]
+    fn ascii_columns_are_bounded() {
+        let img = png(&gradient(40, 20));
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let art = convert_to_ascii(py, &img, 20, None).ok().unwrap();
+            assert!(art.lines().all(|line| line.chars().count() == 20));
+            for cols in [1_000_000_000, u32::MAX] {
+                let error = convert_to_ascii(py, &img, cols, None).err();
+                assert!(error.is_some_and(|e| e.is_instance_of::<pyo3::exceptions::PyValueError>(py)));
+            }
+        });
+    }
+    
+    #[test]
+    fn rgb332_has_at_most_256_colors() {
+        let options = Options { bit_layout: Some(BitLayout::Rgb332), ..options(DitherAlgorithm::FloydSteinberg) };
+        let quantized = convert(noise(128, 128, 11), 256, &options);
+        let colors = distinct_colors(&quantized.image);
+        assert!(colors.len() <= 256 && colors.len() > 128, "{} colors", colors.len());
+        let layout = ColorMatcher::bit_layout(BitLayout::Rgb332);
+        assert!(colors.iter().all(|color| layout.palette().contains(&Rgb(*color))));
+    }
+    
+    #[test]
+    fn sms_preset_has_at_most_64_colors() {
+        let sms = palettes::find("sms").ok().unwrap();
+        assert_eq!(sms.colors.len(), 64);
+        let options = Options { palette_preset: Some(sms), ..options(DitherAlgorithm::FloydSteinberg) };
+        let colors = distinct_colors(&convert(noise(96, 96, 12), 256, &options).image);
+        assert!(colors.len() <= 64);
+        assert!(colors.iter().all(|color| sms.colors.contains(&Rgb(*color))));
+    }
+    
+    #[test]
+    fn dither_clamp_reduces_speckle() {
+        // Dark bluish ramp, where unclamped error throws bright pixels into the shadows
+        let img = ImageBuffer::from_fn(128, 64, |x, y| Rgb([(x / 4 + y / 8) as u8, (x / 3) as u8, (x / 2 + 10) as u8]));
+        // Pixels some channel of which lands far from the source
+        let speckle = |options: &Options| {
+            let output = convert(img.clone(), 16, options).image;
+            img.enumerate_pixels()
+                .filter(|&(x, y, pixel)| (0..3).any(|c| pixel[c].abs_diff(output.get_pixel(x, y)[c]) > 64))
+                .count()
+        };
+        for linear_rgb in [false, true] {
+            let unclamped = speckle(&Options { linear_rgb, ..options(DitherAlgorithm::FloydSteinberg) });
+            let clamped = speckle(&Options { linear_rgb, dither_clamp: true, ..options(DitherAlgorithm::FloydSteinberg) });
+            assert!(clamped * 4 < unclamped, "linear_rgb={}: {} speckles clamped, {} without", linear_rgb, clamped, unclamped);
+        }
+    }
+    
+    #[test]
+    fn supplied_palette_is_used_verbatim() {
+        let custom = vec![Rgb([12, 200, 99]), Rgb([250, 3, 77]), Rgb([90, 90, 91]), Rgb([1, 2, 3]), Rgb([240, 240, 10])];
+        let apple = palettes::find("appleii").ok().unwrap();
+        for options in [
+            Options { custom_palette: Some(custom.clone()), ..options(DitherAlgorithm::FloydSteinberg) },
+            Options { custom_palette: Some(custom.clone()), ..options(DitherAlgorithm::None) },
+            Options { palette_preset: Some(apple), ..options(DitherAlgorithm::FloydSteinberg) },
+        ] {
+            let supplied = options.custom_palette.clone().unwrap_or_else(|| apple.colors.to_vec());
+            let quantized = convert(noise(64, 64, 13), 256, &options);
+            assert!(quantized.palette == supplied);
+            assert!(quantized.image.pixels().all(|pixel| supplied.contains(pixel)));
+        }
+    }
+    
+    #[test]
+    fn snap_to_grid_leaves_no_partial_blocks() {
+        // Neither side divides by 8, the nearest whole blocks are 10x10
+        assert_eq!(Tiling::square(8).snapped(50, 30).width, 10);
+        assert_eq!(Tiling::square(8).snapped(50, 30).height, 10);
+        let options = Options { pixel_size: 8, snap_to_grid: true, ..options(DitherAlgorithm::None) };
+        let output = convert(noise(50, 30, 14), 64, &options).image;
+        assert_eq!(output.dimensions(), (50, 30));
+        assert!(output.enumerate_pixels().all(|(x, y, pixel)| pixel == output.get_pixel(x / 10 * 10, y / 10 * 10)));
+        // Each block is averaged on its own rather than the image collapsing to one color
+        let blocks: HashSet<[u8; 3]> = (0..5).map(|column| output.get_pixel(column * 10, 0).0).collect();
+        assert!(blocks.len() > 1);
+    }
+    
+    #[test]
+    fn indices_fall_within_the_palette() {
+        for algorithm in [DitherAlgorithm::None, DitherAlgorithm::FloydSteinberg, DitherAlgorithm::Ordered, DitherAlgorithm::Riemersma] {
+            let quantized = convert(noise(40, 30, 15), 200, &options(algorithm));
+            assert!(quantized.palette.len() <= 256);
+            assert!(quantized.indices.pixels().all(|index| (index[0] as usize) < quantized.palette.len()));
+        }
+        
+        // Both checks come before numpy is needed
+        let out_of_range = Quantized { indices: ImageBuffer::from_pixel(2, 2, Luma([3])), ..convert(gradient(2, 2), 3, &options(DitherAlgorithm::None)) };
+        let too_many = Quantized { palette: vec![Rgb([0, 0, 0]); 300], ..convert(gradient(2, 2), 3, &options(DitherAlgorithm::None)) };
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            assert!(index_array(py, &out_of_range).is_err_and(|e| e.is_instance_of::<errors::Pixel8BitError>(py)));
+            assert!(index_array(py, &too_many).is_err_and(|e| e.is_instance_of::<errors::EncodeError>(py)));
+        });
+    }
+    
+    #[test]
+    fn dither_per_tile_keeps_error_inside_cells() {
+        // Two images sharing only the 8x8 attribute cell at (8, 8)
+        let (first, second) = (noise(32, 24, 16), noise(32, 24, 17));
+        let in_cell = |x: u32, y: u32| (8..16).contains(&x) && (8..16).contains(&y);
+        let second = ImageBuffer::from_fn(32, 24, |x, y| if in_cell(x, y) { *first.get_pixel(x, y) } else { *second.get_pixel(x, y) });
+        let matcher = ColorMatcher::new(generate_palette(16), DistanceMetric::Rgb);
+        let cell = |indices: &IndexImage| indices.enumerate_pixels().filter(|&(x, y, _)| in_cell(x, y)).map(|(_, _, index)| index[0]).collect::<Vec<_>>();
+        
+        for dither_per_tile in [false, true] {
+            let options = Options { dither_per_tile, attribute_clash: true, ..options(DitherAlgorithm::FloydSteinberg) };
+            let dither = |img| [
+                apply_dithering(img, None, &matcher, &FLOYD_STEINBERG, &options),
+                apply_dithering(img, None, &matcher, &JARVIS_JUDICE_NINKE, &options),
+                apply_riemersma(img, None, &matcher, &options),
+            ];
+            for (a, b) in dither(&first).iter().zip(&dither(&second)) {
+                // Error from the differing neighbours only reaches the cell when it isn't contained
+                assert_eq!(cell(a) == cell(b), dither_per_tile);
+            }
+            let pipeline = |img| convert(img, 16, &options).indices;
+            assert_eq!(cell(&pipeline(first.clone())) == cell(&pipeline(second.clone())), dither_per_tile);
+        }
+        
+        // Teletext cells are applied before resizing, so they stay contained in a resized output too.
+        // At double size the 2x3 mosaic cells at (8, 9) and (10, 9) cover output pixels (16..24, 18..24).
+        let options = Options { dither_per_tile: true, teletext_mosaic: true, target_width: Some(64), ..options(DitherAlgorithm::FloydSteinberg) };
+        let shared = |x: u32, y: u32| (8..12).contains(&x) && (9..12).contains(&y);
+        let second = ImageBuffer::from_fn(32, 24, |x, y| if shared(x, y) { *first.get_pixel(x, y) } else { *second.get_pixel(x, y) });
+        let resized_cell = |indices: &IndexImage| (18..24).flat_map(|y| (16..24).map(move |x| (x, y))).map(|(x, y)| indices.get_pixel(x, y)[0]).collect::<Vec<_>>();
+        let (a, b) = (convert(first.clone(), 16, &options), convert(second, 16, &options));
+        assert_eq!(a.indices.dimensions(), (64, 48));
+        assert_eq!(resized_cell(&a.indices), resized_cell(&b.indices));
+        
+        // Attribute and C64 cells only exist on the resized output, the combination is refused
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            for effect in ["attribute_clash", "c64_multicolor", "teletext_mosaic"] {
+                let kwargs = PyDict::new(py);
+                kwargs.set_item("dither_per_tile", true).unwrap();
+                kwargs.set_item(effect, true).unwrap();
+                kwargs.set_item("target_width", 64).unwrap();
+                let parsed = Options::from_kwargs(true, Some(kwargs));
+                assert_eq!(parsed.is_ok(), effect == "teletext_mosaic", "{}", effect);
+            }
+        });
+    }
+    
+    #[test]
+    fn preserve_extremes_adds_black_and_white() {
+        let (black, white) = (Rgb([0, 0, 0]), Rgb([255, 255, 255]));
+        // Mid-tones only, so no adaptive palette would come up with either extreme by itself
+        let img = ImageBuffer::from_fn(32, 32, |x, y| Rgb([(60 + x * 4) as u8, (80 + y * 3) as u8, 120]));
+        let mut palette = adaptive::median_cut(&img, 8);
+        assert!(!palette.contains(&black) && !palette.contains(&white));
+        
+        // Full palettes give up their two least used entries, the rest stay where they were
+        let full = with_extremes(palette.clone(), &img, 8);
+        assert_eq!(full.len(), 8);
+        assert!(full.contains(&black) && full.contains(&white));
+        assert_eq!(full.iter().zip(&palette).filter(|(a, b)| a == b).count(), 6);
+        // Palettes with room just grow
+        palette.truncate(5);
+        assert_eq!(with_extremes(palette.clone(), &img, 8)[..5], palette[..]);
+        
+        for palette_mode in [PaletteMode::MedianCut, PaletteMode::KMeans, PaletteMode::Octree, PaletteMode::Fixed] {
+            let options = Options { palette_mode, preserve_extremes: true, ..options(DitherAlgorithm::FloydSteinberg) };
+            let quantized = convert(img.clone(), 8, &options);
+            assert!(quantized.palette.contains(&black) && quantized.palette.contains(&white));
+        }
+        
+        // Two colors are just the extremes, one can't hold both
+        let options = Options { palette_mode: PaletteMode::MedianCut, preserve_extremes: true, ..options(DitherAlgorithm::None) };
+        let mut pair = convert(img.clone(), 2, &options).palette;
+        pair.sort_by_key(|color| color.0);
+        assert_eq!(pair, vec![black, white]);
+        let error = convert_image(SourceImage { rgb: img, alpha: None, detail: None }, 1, &options).err();
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| assert!(error.is_some_and(|e| e.is_instance_of::<pyo3::exceptions::PyValueError>(py))));
+    }
+    
+    #[test]
+    fn cycling_gif_tables_are_padded() {
+        let quantized = convert(gradient(16, 4), 4, &options(DitherAlgorithm::None));
+        let cycle = encode::Cycle { frames: 3, start: 0, end: 3, delay: 10 };
+        let gif = encode::encode_cycling_gif(&quantized, None, Some(64), &cycle).ok().unwrap();
+        
+        let mut decoder = gif::DecodeOptions::new().read_info(std::io::Cursor::new(gif)).unwrap();
+        assert_eq!(decoder.global_palette().map(|table| table.len()), Some(64 * 3));
+        let mut frames = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!(frame.palette.as_ref().map(|table| table.len()), Some(64 * 3));
+            frames += 1;
+        }
+        assert_eq!(frames, 3);
+    }
+    
+    #[test]
+    #[cfg(not(feature = "avif"))]
+    fn avif_needs_its_feature() {
+        let error = OutputFormat::from_name("avif").err();
+        pyo3::prepare_freethreaded_python();
+        Python::with_gil(|py| {
+            let error = error.unwrap();
+            assert!(error.is_instance_of::<pyo3::exceptions::PyValueError>(py));
+            assert!(error.value(py).to_string().contains("built with the 'avif' feature"));
+        });
+    }
+    
+    #[test]
+    #[cfg(feature = "avif")]
+    fn avif_output_is_an_avif_file() {
+        let options = Options { output_format: OutputFormat::from_name("avif").ok().unwrap(), ..options(DitherAlgorithm::None) };
+        let opaque = convert(gradient(16, 16), 8, &options);
+        let translucent = Quantized { alpha: Some(ImageBuffer::from_pixel(16, 16, Luma([128]))), ..convert(gradient(16, 16), 8, &options) };
+        for quantized in [opaque, translucent] {
+            let encoded = encode::encode(&quantized, &options).ok().unwrap();
+            assert_eq!(&encoded[4..12], b"ftypavif");
+        }
+    }
+}
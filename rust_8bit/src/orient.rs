@@ -0,0 +1,109 @@
+use image::DynamicImage;
+
+// EXIF tag holding how the stored pixels have to be turned to display upright
+const ORIENTATION_TAG: u16 = 0x0112;
+
+// Turn a decoded image upright according to the EXIF orientation in its encoded bytes.
+// Images without EXIF data, or with an unknown orientation, are returned unchanged.
+pub fn auto_orient(img: DynamicImage, image_data: &[u8]) -> DynamicImage {
+    match exif_orientation(image_data) {
+        Some(2) => img.fliph(),
+        Some(3) => img.rotate180(),
+        Some(4) => img.flipv(),
+        // Transpose and transverse: a quarter turn followed by a mirror
+        Some(5) => img.rotate90().fliph(),
+        Some(6) => img.rotate90(),
+        Some(7) => img.rotate270().fliph(),
+        Some(8) => img.rotate270(),
+        _ => img,
+    }
+}
+
+// Orientation (1-8) from the EXIF block of a JPEG (APP1 segment) or PNG (eXIf chunk)
+fn exif_orientation(data: &[u8]) -> Option<u16> {
+    if data.starts_with(&[0xFF, 0xD8]) {
+        jpeg_exif(data).and_then(tiff_orientation)
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png_exif(data).and_then(tiff_orientation)
+    } else {
+        None
+    }
+}
+
+// Walk the JPEG segments up to the start of the scan data looking for an "Exif" APP1 segment
+fn jpeg_exif(data: &[u8]) -> Option<&[u8]> {
+    let mut pos = 2;
+    while pos + 4 <= data.len() {
+        if data[pos] != 0xFF {
+            return None;
+        }
+        let marker = data[pos + 1];
+        // Fill bytes and markers without a length field
+        if marker == 0xFF {
+            pos += 1;
+            continue;
+        }
+        if marker == 0x01 || (0xD0..=0xD7).contains(&marker) {
+            pos += 2;
+            continue;
+        }
+        // Start of scan or end of image, metadata always comes before these
+        if marker == 0xDA || marker == 0xD9 {
+            return None;
+        }
+        let length = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        let segment = data.get(pos + 4..pos + 2 + length)?;
+        if marker == 0xE1 && segment.starts_with(b"Exif\0\0") {
+            return Some(&segment[6..]);
+        }
+        pos += 2 + length;
+    }
+    None
+}
+
+// Walk the PNG chunks looking for an eXIf chunk
+fn png_exif(data: &[u8]) -> Option<&[u8]> {
+    let mut pos = 8;
+    while pos + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[pos..pos + 4].try_into().ok()?) as usize;
+        let kind = &data[pos + 4..pos + 8];
+        let chunk = data.get(pos + 8..(pos + 8).checked_add(length)?)?;
+        match kind {
+            b"eXIf" => return Some(chunk),
+            b"IEND" => return None,
+            _ => {}
+        }
+        // Data is followed by a 4-byte CRC
+        pos += 12 + length;
+    }
+    None
+}
+
+// Read the orientation entry of the first image directory in a TIFF-structured EXIF block
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let big_endian = match tiff.get(..2)? {
+        b"II" => false,
+        b"MM" => true,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| -> Option<u16> {
+        let bytes = [*tiff.get(offset)?, *tiff.get(offset + 1)?];
+        Some(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    };
+    let u32_at = |offset: usize| -> Option<u32> {
+        let bytes: [u8; 4] = tiff.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    };
+    
+    if u16_at(2)? != 42 {
+        return None;
+    }
+    let directory = u32_at(4)? as usize;
+    let entries = u16_at(directory)? as usize;
+    // Each entry is a tag, a type, a count and a 4-byte value, 12 bytes in all
+    (0..entries)
+        .map(|i| directory + 2 + i * 12)
+        .find(|&entry| u16_at(entry) == Some(ORIENTATION_TAG))
+        .and_then(|entry| u16_at(entry + 8))
+        .filter(|orientation| (1..=8).contains(orientation))
+}
@@ -0,0 +1,159 @@
+use image::Rgb;
+use pyo3::prelude::*;
+
+// A named, fixed color table that replaces the generated palette
+pub struct Preset {
+    pub name: &'static str,
+    pub colors: &'static [Rgb<u8>],
+    // Colors form a dark-to-light ramp and are matched by luminance rather than RGB distance
+    pub luminance_ramp: bool,
+}
+
+// Presets accepted by `palette_name`, in the order they are listed in error messages
+pub const PRESETS: &[Preset] = &[
+    Preset { name: "nes", colors: NES, luminance_ramp: false },
+    Preset { name: "gameboy", colors: GAMEBOY, luminance_ramp: true },
+    Preset { name: "cga", colors: CGA, luminance_ramp: false },
+    Preset { name: "ega", colors: EGA, luminance_ramp: false },
+    Preset { name: "c64", colors: C64, luminance_ramp: false },
+    Preset { name: "pico8", colors: PICO8, luminance_ramp: false },
+    Preset { name: "websafe", colors: WEBSAFE, luminance_ramp: false },
+    Preset { name: "zxspectrum", colors: ZX_SPECTRUM, luminance_ramp: false },
+    Preset { name: "sms", colors: SMS, luminance_ramp: false },
+    Preset { name: "appleii", colors: APPLE_II, luminance_ramp: false },
+    Preset { name: "msx", colors: MSX, luminance_ramp: false },
+    Preset { name: "teletext", colors: TELETEXT, luminance_ramp: false },
+];
+
+// Look up a preset by name
+pub fn find(name: &str) -> PyResult<&'static Preset> {
+    PRESETS.iter()
+        .find(|preset| preset.name == name)
+        .ok_or_else(|| {
+            let valid: Vec<&str> = PRESETS.iter().map(|preset| preset.name).collect();
+            PyErr::new::<crate::errors::InvalidPaletteError, _>(format!("Unknown palette name '{}', expected one of: {}", name, valid.join(", ")))
+        })
+}
+
+// NES (2C02 PPU) master palette: the 54 distinct colors the console can display
+pub const NES: &[Rgb<u8>] = &[
+    // $00-$0C
+    Rgb([124, 124, 124]), Rgb([0, 0, 252]), Rgb([0, 0, 188]), Rgb([68, 40, 188]),
+    Rgb([148, 0, 132]), Rgb([168, 0, 32]), Rgb([168, 16, 0]), Rgb([136, 20, 0]),
+    Rgb([80, 48, 0]), Rgb([0, 120, 0]), Rgb([0, 104, 0]), Rgb([0, 88, 0]),
+    Rgb([0, 64, 88]),
+    // $10-$1C
+    Rgb([188, 188, 188]), Rgb([0, 120, 248]), Rgb([0, 88, 248]), Rgb([104, 68, 252]),
+    Rgb([216, 0, 204]), Rgb([228, 0, 88]), Rgb([248, 56, 0]), Rgb([228, 92, 16]),
+    Rgb([172, 124, 0]), Rgb([0, 184, 0]), Rgb([0, 168, 0]), Rgb([0, 168, 68]),
+    Rgb([0, 136, 136]),
+    // $20-$2C
+    Rgb([248, 248, 248]), Rgb([60, 188, 252]), Rgb([104, 136, 252]), Rgb([152, 120, 248]),
+    Rgb([248, 120, 248]), Rgb([248, 88, 152]), Rgb([248, 120, 88]), Rgb([252, 160, 68]),
+    Rgb([248, 184, 0]), Rgb([184, 248, 24]), Rgb([88, 216, 84]), Rgb([88, 248, 152]),
+    Rgb([0, 232, 216]),
+    // $30-$3C
+    Rgb([252, 252, 252]), Rgb([164, 228, 252]), Rgb([184, 184, 248]), Rgb([216, 184, 248]),
+    Rgb([248, 184, 248]), Rgb([248, 164, 192]), Rgb([240, 208, 176]), Rgb([252, 224, 168]),
+    Rgb([248, 216, 120]), Rgb([216, 248, 120]), Rgb([184, 248, 184]), Rgb([184, 248, 216]),
+    Rgb([0, 252, 252]),
+    // $2D dark grey and black ($0D-$0F and the other column E/F entries all collapse to black)
+    Rgb([120, 120, 120]), Rgb([0, 0, 0]),
+];
+
+// Original Game Boy (DMG) screen: four shades of green from darkest to lightest
+pub const GAMEBOY: &[Rgb<u8>] = &[
+    Rgb([15, 56, 15]), Rgb([48, 98, 48]), Rgb([139, 172, 15]), Rgb([155, 188, 15]),
+];
+
+// IBM CGA 16-color IRGB set, in color-number order
+pub const CGA: &[Rgb<u8>] = &[
+    Rgb([0, 0, 0]), Rgb([0, 0, 170]), Rgb([0, 170, 0]), Rgb([0, 170, 170]),
+    Rgb([170, 0, 0]), Rgb([170, 0, 170]), Rgb([170, 85, 0]), Rgb([170, 170, 170]),
+    Rgb([85, 85, 85]), Rgb([85, 85, 255]), Rgb([85, 255, 85]), Rgb([85, 255, 255]),
+    Rgb([255, 85, 85]), Rgb([255, 85, 255]), Rgb([255, 255, 85]), Rgb([255, 255, 255]),
+];
+
+// CGA 320x200 4-color mode, high intensity: palette 0 (green/red/yellow) and palette 1 (cyan/magenta/white)
+pub const CGA_MODE4: [&[Rgb<u8>]; 2] = [
+    &[Rgb([0, 0, 0]), Rgb([85, 255, 85]), Rgb([255, 85, 85]), Rgb([255, 255, 85])],
+    &[Rgb([0, 0, 0]), Rgb([85, 255, 255]), Rgb([255, 85, 255]), Rgb([255, 255, 255])],
+];
+
+// The EGA's default 16-color palette reproduces the CGA colors
+pub const EGA: &[Rgb<u8>] = CGA;
+
+// Commodore 64 VIC-II colors (Pepto's measured values), in color-number order
+pub const C64: &[Rgb<u8>] = &[
+    Rgb([0, 0, 0]), Rgb([255, 255, 255]), Rgb([104, 55, 43]), Rgb([112, 164, 178]),
+    Rgb([111, 61, 134]), Rgb([88, 141, 67]), Rgb([53, 40, 121]), Rgb([184, 199, 111]),
+    Rgb([111, 79, 37]), Rgb([67, 57, 0]), Rgb([154, 103, 89]), Rgb([68, 68, 68]),
+    Rgb([108, 108, 108]), Rgb([154, 210, 132]), Rgb([108, 94, 181]), Rgb([149, 149, 149]),
+];
+
+// PICO-8 fantasy console palette, in color-number order
+pub const PICO8: &[Rgb<u8>] = &[
+    Rgb([0, 0, 0]), Rgb([29, 43, 83]), Rgb([126, 37, 83]), Rgb([0, 135, 81]),
+    Rgb([171, 82, 54]), Rgb([95, 87, 79]), Rgb([194, 195, 199]), Rgb([255, 241, 232]),
+    Rgb([255, 0, 77]), Rgb([255, 163, 0]), Rgb([255, 236, 39]), Rgb([0, 228, 54]),
+    Rgb([41, 173, 255]), Rgb([131, 118, 156]), Rgb([255, 119, 168]), Rgb([255, 204, 170]),
+];
+
+// ZX Spectrum ULA colors: the eight basic colors at normal intensity, then the seven that are
+// brighter with the BRIGHT bit set (black looks the same either way), in color-number order
+pub const ZX_SPECTRUM: &[Rgb<u8>] = &[
+    Rgb([0, 0, 0]), Rgb([0, 0, 215]), Rgb([215, 0, 0]), Rgb([215, 0, 215]),
+    Rgb([0, 215, 0]), Rgb([0, 215, 215]), Rgb([215, 215, 0]), Rgb([215, 215, 215]),
+    Rgb([0, 0, 255]), Rgb([255, 0, 0]), Rgb([255, 0, 255]),
+    Rgb([0, 255, 0]), Rgb([0, 255, 255]), Rgb([255, 255, 0]), Rgb([255, 255, 255]),
+];
+
+// Web-safe color cube: every combination of 0, 51, 102, 153, 204 and 255 per channel, red-major
+pub const WEBSAFE: &[Rgb<u8>] = &websafe_cube();
+
+const fn websafe_cube() -> [Rgb<u8>; 216] {
+    let mut colors = [Rgb([0, 0, 0]); 216];
+    let mut i = 0;
+    while i < 216 {
+        colors[i] = Rgb([(i / 36) as u8 * 51, (i / 6 % 6) as u8 * 51, (i % 6) as u8 * 51]);
+        i += 1;
+    }
+    colors
+}
+
+// Sega Master System: 2 bits per channel, every combination of 0, 85, 170 and 255, red-major.
+// The same colors as `bit_layout="rgb222"`.
+pub const SMS: &[Rgb<u8>] = &sms_cube();
+
+const fn sms_cube() -> [Rgb<u8>; 64] {
+    let mut colors = [Rgb([0, 0, 0]); 64];
+    let mut i = 0;
+    while i < 64 {
+        colors[i] = Rgb([(i / 16) as u8 * 85, (i / 4 % 4) as u8 * 85, (i % 4) as u8 * 85]);
+        i += 1;
+    }
+    colors
+}
+
+// Apple II 16-color (lo-res and double hi-res) NTSC colors, in color-number order. Colors 5 and 10
+// are the same gray, so it is listed once. The 6 colors of plain hi-res mode are among these.
+pub const APPLE_II: &[Rgb<u8>] = &[
+    Rgb([0, 0, 0]), Rgb([227, 30, 96]), Rgb([96, 78, 189]), Rgb([255, 68, 253]),
+    Rgb([0, 163, 96]), Rgb([156, 156, 156]), Rgb([20, 207, 253]), Rgb([208, 195, 255]),
+    Rgb([96, 114, 3]), Rgb([255, 106, 60]), Rgb([255, 160, 208]),
+    Rgb([20, 245, 60]), Rgb([208, 221, 141]), Rgb([114, 255, 208]), Rgb([255, 255, 255]),
+];
+
+// MSX1 (TI TMS9918 VDP) colors, in color-number order from 1. Color 0 is transparent and left out.
+pub const MSX: &[Rgb<u8>] = &[
+    Rgb([0, 0, 0]), Rgb([33, 200, 66]), Rgb([94, 220, 120]), Rgb([84, 85, 237]),
+    Rgb([125, 118, 252]), Rgb([212, 82, 77]), Rgb([66, 235, 245]), Rgb([252, 85, 84]),
+    Rgb([255, 121, 120]), Rgb([212, 193, 84]), Rgb([230, 206, 128]), Rgb([33, 176, 59]),
+    Rgb([201, 91, 186]), Rgb([204, 204, 204]), Rgb([255, 255, 255]),
+];
+
+// Teletext (Mullard SAA5050) colors: the corners of the RGB cube at full intensity, in color-number order
+pub const TELETEXT: &[Rgb<u8>] = &[
+    Rgb([0, 0, 0]), Rgb([255, 0, 0]), Rgb([0, 255, 0]), Rgb([255, 255, 0]),
+    Rgb([0, 0, 255]), Rgb([255, 0, 255]), Rgb([0, 255, 255]), Rgb([255, 255, 255]),
+];
@@ -0,0 +1,169 @@
+use image::{ImageBuffer, Pixel};
+use pyo3::prelude::*;
+use crate::color::{linear_to_srgb, srgb_to_linear};
+
+// Cell shapes the image can be averaged into
+#[derive(Clone, Copy, PartialEq)]
+pub enum TileShape {
+    Rectangle,
+    Hexagon,
+}
+
+// Names accepted by `tile_shape`, in the order they are listed in error messages
+pub const TILE_SHAPES: &[(&str, TileShape)] = &[
+    ("rectangle", TileShape::Rectangle),
+    ("hexagon", TileShape::Hexagon),
+];
+
+impl TileShape {
+    pub fn from_name(name: &str) -> PyResult<Self> {
+        TILE_SHAPES.iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|&(_, shape)| shape)
+            .ok_or_else(|| {
+                let valid: Vec<&str> = TILE_SHAPES.iter().map(|(candidate, _)| *candidate).collect();
+                PyErr::new::<pyo3::exceptions::PyValueError, _>(format!("Unknown tile shape '{}', expected one of: {}", name, valid.join(", ")))
+            })
+    }
+}
+
+// A grid of `width` x `height` cells covering the image. Each cell becomes one pixel of the
+// downsampled image, laid out by column and row so neighbouring cells stay neighbours.
+//
+// Hexagon cells are pointy-topped: rows sit 3/4 of a cell apart and every odd row is shifted right by
+// half a cell. A pixel belongs to the hexagon whose center is nearest once the lattice is scaled to
+// be equilateral, which gives cells that are `width` wide and `height` tall.
+#[derive(Clone, Copy)]
+pub struct Tiling {
+    pub width: u32,
+    pub height: u32,
+    pub shape: TileShape,
+}
+
+impl Tiling {
+    pub fn square(size: u32) -> Self {
+        Tiling { width: size, height: size, shape: TileShape::Rectangle }
+    }
+    
+    // The rectangular cells nearest in size to these that divide a `width` x `height` image exactly,
+    // so there are no partial cells along the right and bottom edges. Ties go to the larger cell.
+    pub fn snapped(self, width: u32, height: u32) -> Self {
+        let nearest_divisor = |size: u32, length: u32| {
+            (1..=length.max(1))
+                .filter(|&d| length.is_multiple_of(d))
+                .min_by_key(|&d| (d.abs_diff(size), u32::MAX - d))
+                .unwrap_or(1)
+        };
+        Tiling { width: nearest_divisor(self.width, width), height: nearest_divisor(self.height, height), shape: self.shape }
+    }
+    
+    // Vertical distance between hexagon rows
+    fn row_step(&self) -> f32 {
+        self.height as f32 * 0.75
+    }
+    
+    // Number of cell columns and rows needed to cover a `width` x `height` image
+    fn grid(&self, width: u32, height: u32) -> (u32, u32) {
+        match self.shape {
+            TileShape::Rectangle => (width.div_ceil(self.width), height.div_ceil(self.height)),
+            TileShape::Hexagon => {
+                let columns = ((width as f32 - 0.5) / self.width as f32).round() as u32 + 1;
+                let rows = ((height as f32 - 0.5) / self.row_step()).floor() as u32 + 2;
+                (columns, rows)
+            }
+        }
+    }
+    
+    // Center of a hexagon cell in pixel coordinates
+    fn center(&self, column: u32, row: u32) -> (f32, f32) {
+        let shift = if row % 2 == 1 { self.width as f32 / 2.0 } else { 0.0 };
+        (column as f32 * self.width as f32 + shift, row as f32 * self.row_step())
+    }
+    
+    // Column and row of the cell pixel (x, y) falls in
+    fn cell(&self, x: u32, y: u32) -> (u32, u32) {
+        match self.shape {
+            TileShape::Rectangle => (x / self.width, y / self.height),
+            TileShape::Hexagon => {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                // Vertical distances shrink so neighbouring centers are all one unit apart
+                let vertical_scale = self.width as f32 / (self.row_step() * 2.0 / 3f32.sqrt());
+                let first_row = (py / self.row_step()).floor() as u32;
+                // The nearest center is always in one of the two rows either side of the pixel
+                [first_row, first_row + 1]
+                    .map(|row| {
+                        let (shift, _) = self.center(0, row);
+                        let column = ((px - shift) / self.width as f32).round().max(0.0) as u32;
+                        let (cx, cy) = self.center(column, row);
+                        let (dx, dy) = (px - cx, (py - cy) * vertical_scale);
+                        ((column, row), dx * dx + dy * dy)
+                    })
+                    .into_iter()
+                    .min_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|(cell, _)| cell)
+                    .unwrap_or((0, 0))
+            }
+        }
+    }
+}
+
+// Shrink an image by averaging the pixels of each cell of `tiling` into a single pixel.
+// Cells along the edges may be partial and average whatever pixels they cover. Hexagon cells that
+// cover no pixel at all take the color of the pixel nearest their center.
+// With `linear` the channels are averaged in linear light, so a black and white block comes out
+// as the ~188 grey it looks like from a distance rather than 128.
+pub fn downsample<P: Pixel<Subpixel = u8>>(img: &ImageBuffer<P, Vec<u8>>, tiling: Tiling, linear: bool) -> ImageBuffer<P, Vec<u8>> {
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return ImageBuffer::new(0, 0);
+    }
+    let (small_width, small_height) = tiling.grid(width, height);
+    let channels = P::CHANNEL_COUNT as usize;
+    
+    let mut sums = vec![([0f32; 4], 0u32); (small_width * small_height) as usize];
+    for (x, y, pixel) in img.enumerate_pixels() {
+        let (column, row) = tiling.cell(x, y);
+        let (sum, count) = &mut sums[(row * small_width + column) as usize];
+        let pixel = pixel.channels();
+        for c in 0..channels {
+            sum[c] += if linear { srgb_to_linear(pixel[c]) } else { pixel[c] as f32 };
+        }
+        *count += 1;
+    }
+    
+    ImageBuffer::from_fn(small_width, small_height, |column, row| {
+        let (sum, count) = sums[(row * small_width + column) as usize];
+        if count == 0 {
+            let (cx, cy) = tiling.center(column, row);
+            return *img.get_pixel((cx as u32).min(width - 1), (cy as u32).min(height - 1));
+        }
+        let average = sum.map(|s| {
+            let mean = s / count as f32;
+            if linear { linear_to_srgb(mean) } else { mean.round() as u8 }
+        });
+        *P::from_slice(&average[..channels])
+    })
+}
+
+// Blow a downsampled image back up to `width` x `height`, filling every pixel with the value of
+// its cell so each cell stays a crisp shape of one color
+pub fn upscale<P: Pixel>(img: &ImageBuffer<P, Vec<P::Subpixel>>, tiling: Tiling, width: u32, height: u32) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let (column, row) = tiling.cell(x, y);
+        *img.get_pixel(column, row)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+    
+    #[test]
+    fn linear_averaging_brightens_mixed_blocks() {
+        let checkerboard = ImageBuffer::from_fn(4, 4, |x, y| if (x + y) % 2 == 0 { Rgb([0u8; 3]) } else { Rgb([255; 3]) });
+        let grey = |linear| downsample(&checkerboard, Tiling::square(2), linear).get_pixel(1, 1)[0];
+        assert!((127..=128).contains(&grey(false)));
+        assert!((186..=189).contains(&grey(true)), "linear average {}", grey(true));
+    }
+}
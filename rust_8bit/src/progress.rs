@@ -0,0 +1,34 @@
+use pyo3::prelude::*;
+use std::sync::Mutex;
+
+// A Python callable told how far a conversion has got, as a fraction from 0.0 to 1.0.
+// Conversions run with the GIL released, so it is only taken back for the call itself.
+pub struct Progress {
+    callback: PyObject,
+    // First exception the callback raised, later reports are skipped once it is set
+    error: Mutex<Option<PyErr>>,
+}
+
+impl Progress {
+    pub fn new(callback: PyObject) -> Self {
+        Progress { callback, error: Mutex::new(None) }
+    }
+    
+    pub fn report(&self, fraction: f32) {
+        let mut error = self.error.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if error.is_some() {
+            return;
+        }
+        if let Err(e) = Python::with_gil(|py| self.callback.call1(py, (fraction.clamp(0.0, 1.0),))) {
+            *error = Some(e);
+        }
+    }
+    
+    // Hand back the exception raised by the callback, so the conversion stops with it
+    pub fn check(&self) -> PyResult<()> {
+        match self.error.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use image::Rgb;
+
+// Nearest palette color by plain RGB distance, four palette colors per step. Built with the "simd"
+// feature; on x86_64 it uses SSE2 (always present there), elsewhere it falls back to the scalar
+// search. Either way the result is the same as `find_nearest_index`, including which of several
+// equally near colors wins (the first).
+pub struct PackedPalette {
+    // 16-bit lanes: red and green of every color interleaved, and blue followed by a zero, so one
+    // multiply-add per vector sums the squared differences of a color's channels
+    red_green: Vec<i16>,
+    blue: Vec<i16>,
+}
+
+impl PackedPalette {
+    pub fn new(palette: &[Rgb<u8>]) -> Self {
+        PackedPalette {
+            red_green: palette.iter().flat_map(|c| [c[0] as i16, c[1] as i16]).collect(),
+            blue: palette.iter().flat_map(|c| [c[2] as i16, 0]).collect(),
+        }
+    }
+    
+    pub fn nearest_index(&self, pixel: &Rgb<u8>, palette: &[Rgb<u8>]) -> usize {
+        let blocks = palette.len() / 4;
+        let (mut best_distance, mut best_index) = self.nearest_in_blocks(pixel, blocks);
+        // Colors past the last whole block of four are compared one at a time
+        for (index, color) in palette.iter().enumerate().skip(blocks * 4) {
+            let distance = crate::color_distance(pixel, color);
+            if distance < best_distance {
+                best_distance = distance;
+                best_index = index;
+            }
+        }
+        best_index
+    }
+    
+    // Distance to and position of the nearest of the first `blocks` * 4 colors
+    #[cfg(target_arch = "x86_64")]
+    fn nearest_in_blocks(&self, pixel: &Rgb<u8>, blocks: usize) -> (u32, usize) {
+        use std::arch::x86_64::*;
+        
+        let mut distances = [0i32; 4];
+        let mut indices = [0i32; 4];
+        // SAFETY: SSE2 is part of the x86_64 baseline, and every load reads 8 lanes of a block that
+        // lies entirely inside the packed vectors
+        unsafe {
+            let red_green = _mm_set1_epi32(((pixel[1] as i32) << 16) | pixel[0] as i32);
+            let blue = _mm_set1_epi32(pixel[2] as i32);
+            let mut best = _mm_set1_epi32(i32::MAX);
+            let mut best_index = _mm_setzero_si128();
+            let mut index = _mm_setr_epi32(0, 1, 2, 3);
+            let step = _mm_set1_epi32(4);
+            for block in 0..blocks {
+                let rg = _mm_loadu_si128(self.red_green.as_ptr().add(block * 8) as *const __m128i);
+                let b = _mm_loadu_si128(self.blue.as_ptr().add(block * 8) as *const __m128i);
+                let d_rg = _mm_sub_epi16(rg, red_green);
+                let d_b = _mm_sub_epi16(b, blue);
+                let distance = _mm_add_epi32(_mm_madd_epi16(d_rg, d_rg), _mm_madd_epi16(d_b, d_b));
+                // Strictly closer only, so each lane keeps the first of equally near colors
+                let closer = _mm_cmplt_epi32(distance, best);
+                best = _mm_or_si128(_mm_and_si128(closer, distance), _mm_andnot_si128(closer, best));
+                best_index = _mm_or_si128(_mm_and_si128(closer, index), _mm_andnot_si128(closer, best_index));
+                index = _mm_add_epi32(index, step);
+            }
+            _mm_storeu_si128(distances.as_mut_ptr() as *mut __m128i, best);
+            _mm_storeu_si128(indices.as_mut_ptr() as *mut __m128i, best_index);
+        }
+        
+        distances.iter().zip(indices)
+            .map(|(&distance, index)| (distance as u32, index as usize))
+            .min()
+            .unwrap_or((u32::MAX, 0))
+    }
+    
+    // No vector path on other targets, the block colors are compared one at a time
+    #[cfg(not(target_arch = "x86_64"))]
+    fn nearest_in_blocks(&self, pixel: &Rgb<u8>, blocks: usize) -> (u32, usize) {
+        (0..blocks * 4)
+            .map(|i| {
+                let color = Rgb([self.red_green[2 * i] as u8, self.red_green[2 * i + 1] as u8, self.blue[2 * i] as u8]);
+                (crate::color_distance(pixel, &color), i)
+            })
+            .min()
+            .unwrap_or((u32::MAX, 0))
+    }
+}
+
+#[cfg(all(test, feature = "simd"))]
+mod tests {
+    use super::*;
+    use crate::color::{ColorMatcher, DistanceMetric};
+    
+    #[test]
+    fn matches_the_scalar_search() {
+        let mut state = 0;
+        // Random color with each channel masked by `mask`
+        let mut next = |mask: u8| {
+            state += 1;
+            let bytes = crate::mix(state).to_le_bytes();
+            Rgb([bytes[0] & mask, bytes[1] & mask, bytes[2] & mask])
+        };
+        // Every remainder of blocks of four, with coarse channels so equally near colors are common
+        for size in 1..=67 {
+            let palette: Vec<Rgb<u8>> = (0..size).map(|_| next(0xE0)).collect();
+            let packed = PackedPalette::new(&palette);
+            let matcher = ColorMatcher::new(palette.clone(), DistanceMetric::Rgb);
+            for _ in 0..200 {
+                let pixel = next(0xFF);
+                let expected = crate::find_nearest_index(&pixel, &palette);
+                assert_eq!(packed.nearest_index(&pixel, &palette), expected);
+                assert_eq!(matcher.nearest_index(&pixel), expected);
+            }
+        }
+    }
+}
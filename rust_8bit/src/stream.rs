@@ -0,0 +1,100 @@
+use image::{DynamicImage, Rgb};
+use pyo3::prelude::*;
+use std::io::Write;
+use crate::encode::{self, OutputFormat};
+use crate::{adjust, DitherAlgorithm, Options};
+
+// Strips are rounded up to this many rows so ordered dithering thresholds line up across them
+const STRIP_ALIGNMENT: u32 = 8;
+
+// Convert and encode `img` a strip of `options.strip_height` rows at a time, returning the PNG bytes
+// and the palette. Only the decoded image is ever held in full: each strip is converted to RGB,
+// adjusted, matched and written out before the next is touched.
+//
+// Every pixel has to be matched on its own for this to work, so error diffusion (which carries
+// error from row to row) and anything else that needs the whole image are rejected by `Options`.
+pub fn convert_in_strips(img: &DynamicImage, palette_size: usize, options: &Options) -> PyResult<(Vec<u8>, Vec<Rgb<u8>>)> {
+    // There is no whole image to estimate the palette size from
+    if palette_size == crate::AUTO_PALETTE_SIZE {
+        return Err(PyErr::new::<pyo3::exceptions::PyValueError, _>("palette_size 'auto' cannot be combined with strip_height"));
+    }
+    let strip_height = options.strip_height.unwrap_or(u32::MAX).div_ceil(STRIP_ALIGNMENT).saturating_mul(STRIP_ALIGNMENT);
+    let (width, height) = (img.width(), img.height());
+    
+    // Only adaptive palettes look at the pixels and those are rejected, so nothing is lost by
+    // building the matcher from an empty image
+    let matcher = crate::build_matcher(&mut image::ImageBuffer::new(0, 0), palette_size, options)?;
+    let mut palette = crate::output_palette(&matcher, options);
+    if let Some(recolor) = &options.recolor {
+        for color in palette.iter_mut() {
+            if let Some(&replacement) = recolor.get(color) {
+                *color = replacement;
+            }
+        }
+    }
+    
+    if options.gamma != 1.0 {
+        let table = crate::effects::gamma_table(options.gamma);
+        for color in palette.iter_mut() {
+            *color = Rgb(color.0.map(|c| table[c as usize]));
+        }
+    }
+    
+    let spread = crate::ordered_spread(&matcher, options);
+    
+    let indexed = options.output_format == OutputFormat::PngIndexed;
+    let to_err = |e: png::EncodingError| PyErr::new::<crate::errors::EncodeError, _>(format!("Failed to encode image: {}", e));
+    let mut output_bytes = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut output_bytes, width, height);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_compression(options.png_compression.level());
+        let table = if indexed {
+            let (table, transparent_index) = encode::byte_table(&palette, options.transparent_color, "Indexed PNG")?;
+            encoder.set_color(png::ColorType::Indexed);
+            encoder.set_palette(encode::padded_color_table(&palette, options.pad_palette_to)?);
+            if let Some(t) = transparent_index {
+                encoder.set_trns(encode::transparency_chunk(t));
+            }
+            table
+        } else {
+            encoder.set_color(png::ColorType::Rgb);
+            Vec::new()
+        };
+        let mut writer = encoder.write_header().map_err(to_err)?;
+        let mut stream = writer.stream_writer().map_err(to_err)?;
+        
+        let mut row_bytes = Vec::with_capacity(width as usize * 3);
+        for top in (0..height).step_by(strip_height as usize) {
+            options.report(top as f32 / height as f32);
+            let rows = strip_height.min(height - top);
+            let mut strip = img.crop_imm(0, top, width, rows).to_rgb8();
+            adjust::adjust(&mut strip, options);
+            if crate::matches_luminance(options) {
+                strip = crate::to_grayscale(&strip);
+            }
+            if options.predither_noise > 0.0 {
+                crate::add_predither_noise(&mut strip, top, options);
+            }
+            
+            let indices = match options.dither_algorithm {
+                DitherAlgorithm::Ordered => crate::apply_ordered_dithering(&strip, &matcher, options.bayer_size, spread),
+                _ => crate::map_pixels(&strip, |_, _, pixel| matcher.nearest_index(pixel) as u16),
+            };
+            for row in indices.as_raw().chunks(width.max(1) as usize) {
+                row_bytes.clear();
+                if indexed {
+                    row_bytes.extend(row.iter().map(|&index| table[index as usize]));
+                } else {
+                    row_bytes.extend(row.iter().flat_map(|&index| palette[index as usize].0));
+                }
+                stream.write_all(&row_bytes).map_err(|e| PyErr::new::<crate::errors::EncodeError, _>(format!("Failed to encode image: {}", e)))?;
+            }
+            options.check_progress()?;
+        }
+        stream.finish().map_err(to_err)?;
+    }
+    options.report(1.0);
+    
+    Ok((output_bytes, palette))
+}
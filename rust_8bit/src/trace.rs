@@ -0,0 +1,41 @@
+// Pipeline stages as `tracing` spans, built with the "trace" feature. Each stage is a
+// "rust_8bit" info span with its `stage` name as a field, so an application can collect them with
+// any subscriber it installs. `trace=True` installs one that prints how long each stage took to
+// stderr, unless a global subscriber is already set. Without the feature `Stage` is empty and
+// starting one compiles to nothing.
+
+// A running stage, its span is closed when it is dropped
+pub struct Stage {
+    #[cfg(feature = "trace")]
+    _span: tracing::span::EnteredSpan,
+}
+
+impl Stage {
+    #[inline]
+    pub fn start(name: &'static str) -> Self {
+        #[cfg(feature = "trace")]
+        {
+            Stage { _span: tracing::info_span!("rust_8bit", stage = name).entered() }
+        }
+        #[cfg(not(feature = "trace"))]
+        {
+            let _ = name;
+            Stage {}
+        }
+    }
+}
+
+// Print every closed span with its duration to stderr from now on. Only the first call installs the
+// subscriber, and none is installed over one the application set itself.
+#[cfg(feature = "trace")]
+pub fn print_to_stderr() {
+    static INSTALL: std::sync::Once = std::sync::Once::new();
+    INSTALL.call_once(|| {
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+            .with_target(false)
+            .finish();
+        let _ = tracing::subscriber::set_global_default(subscriber);
+    });
+}